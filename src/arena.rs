@@ -0,0 +1,907 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, STARTING_FEN_STRING},
+    epd::Epd,
+    movegen::{game_status, parse_san, parse_uci_move, to_san, GameStatus},
+    pgn::{parse_pgn, GameResult},
+    piece::Color,
+    sprt::{Score, Sprt, SprtResult},
+};
+
+// How long an engine gets to answer anything other than a search, and how
+// far past its clock a move may arrive before it counts as a time loss.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const TIME_MARGIN: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    // Seconds, as in "10+0.1" or "60".
+    pub fn parse(text: &str) -> Option<Self> {
+        let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+        let seconds = |text: &str| {
+            let value: f64 = text.parse().ok()?;
+            (value >= 0.0).then(|| Duration::from_secs_f64(value))
+        };
+
+        Some(Self {
+            base: seconds(base)?,
+            increment: seconds(increment)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EngineConfig {
+    pub command: Vec<String>,
+    pub options: Vec<(String, String)>,
+}
+
+// A UCI engine running as a child process. Its output is read on a separate
+// thread so that waiting for a move can time out.
+pub struct Engine {
+    pub name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl Engine {
+    pub fn start(config: &EngineConfig) -> io::Result<Self> {
+        let (program, args) = config
+            .command
+            .split_first()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "empty engine command"))?;
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Self {
+            name: program.clone(),
+            child,
+            stdin,
+            lines,
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.read_line(HANDSHAKE_TIMEOUT)?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+
+        for (name, value) in &config.options {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.wait_ready()?;
+
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    fn read_line(&mut self, timeout: Duration) -> io::Result<String> {
+        self.lines
+            .recv_timeout(timeout)
+            .map_err(|error| match error {
+                RecvTimeoutError::Timeout => {
+                    io::Error::new(ErrorKind::TimedOut, "engine timed out")
+                }
+                RecvTimeoutError::Disconnected => {
+                    io::Error::new(ErrorKind::BrokenPipe, "engine disconnected")
+                }
+            })
+    }
+
+    fn wait_ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        while self.read_line(HANDSHAKE_TIMEOUT)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    // Searches the position and returns the best move as sent, giving up
+    // once `timeout` has passed.
+    pub fn go(&mut self, position: &str, go: &str, timeout: Duration) -> io::Result<String> {
+        self.send(position)?;
+        self.send(go)?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = match self.read_line(remaining) {
+                Ok(line) => line,
+                Err(error) => {
+                    if error.kind() == ErrorKind::TimedOut && self.drain_search().is_err() {
+                        self.kill();
+                    }
+                    return Err(error);
+                }
+            };
+
+            if let Some(rest) = line.strip_prefix("bestmove") {
+                return Ok(rest.split_whitespace().next().unwrap_or("").to_string());
+            }
+        }
+    }
+
+    // Stops a search that ran out of time and throws its best move away, so
+    // it can't be taken for a move in the next game.
+    fn drain_search(&mut self) -> io::Result<()> {
+        self.send("stop")?;
+        while !self.read_line(HANDSHAKE_TIMEOUT)?.starts_with("bestmove") {}
+        Ok(())
+    }
+
+    // Ends an engine that can't be trusted to answer, so that it gets
+    // restarted for its next game.
+    pub fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// A start position and the moves played from it, in UCI notation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<String>,
+}
+
+impl Default for Opening {
+    fn default() -> Self {
+        Self {
+            fen: STARTING_FEN_STRING.to_string(),
+            moves: Vec::new(),
+        }
+    }
+}
+
+// Reads openings from PGN games, or from one FEN or EPD position per line.
+// The text is PGN if it starts with a tag or with a line that isn't a
+// position.
+pub fn parse_openings(text: &str) -> Vec<Opening> {
+    let first_line = text.lines().map(str::trim).find(|line| !line.is_empty());
+    let is_pgn = first_line.is_some_and(|line| {
+        line.starts_with('[') || Epd::parse(line).and_then(|epd| epd.board()).is_none()
+    });
+
+    if is_pgn {
+        return parse_pgn(text)
+            .iter()
+            .filter_map(|game| {
+                let mut board = game.start_position()?;
+                let fen = board.to_fen();
+                let mut moves = Vec::new();
+
+                for san in &game.moves {
+                    let chess_move = parse_san(&mut board, san)?;
+                    moves.push(chess_move.to_uci());
+                    board.make_move(&chess_move);
+                }
+                Some(Opening { fen, moves })
+            })
+            .collect();
+    }
+
+    text.lines()
+        .filter_map(|line| {
+            let board = Epd::parse(line)?.board()?;
+            Some(Opening {
+                fen: board.to_fen(),
+                moves: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+// Neither side can ever mate: bare kings, or a single minor piece.
+fn insufficient_material(board: &Board) -> bool {
+    let count = |kind: usize| board.pieces[kind].count() + board.pieces[6 + kind].count();
+
+    count(0) + count(3) + count(4) == 0 && count(1) + count(2) <= 1
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    pub fen: String,
+    pub moves: Vec<String>,
+    pub result: GameResult,
+    pub termination: String,
+}
+
+impl GameRecord {
+    pub fn to_pgn(&self, round: usize) -> String {
+        let result = self.result.token();
+        let mut pgn = format!(
+            "[Event \"Engine match\"]\n[Round \"{}\"]\n[White \"{}\"]\n[Black \"{}\"]\n[Result \"{}\"]\n",
+            round, self.white, self.black, result
+        );
+        if self.fen != STARTING_FEN_STRING {
+            pgn.push_str(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", self.fen));
+        }
+        pgn.push_str(&format!("[Termination \"{}\"]\n\n", self.termination));
+
+        let board = Board::from_fen(&self.fen).unwrap();
+        let mut number = board.fullmove_number;
+        let mut line = String::new();
+        let mut words = Vec::new();
+
+        for (ply, san) in self.moves.iter().enumerate() {
+            let white_to_move = (ply % 2 == 0) == (board.side_to_move == Color::White);
+            if white_to_move {
+                words.push(format!("{}. {}", number, san));
+            } else {
+                if ply == 0 {
+                    words.push(format!("{}... {}", number, san));
+                } else {
+                    words.push(san.clone());
+                }
+                number += 1;
+            }
+        }
+        words.push(result.to_string());
+
+        // Movetext wrapped at 80 columns.
+        for word in words {
+            if !line.is_empty() && line.len() + word.len() + 1 > 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        pgn.push_str(&line);
+        pgn.push_str("\n\n");
+
+        pgn
+    }
+
+    // Points scored by White.
+    pub fn white_points(&self) -> f64 {
+        match self.result {
+            GameResult::WhiteWins => 1.0,
+            GameResult::BlackWins => 0.0,
+            _ => 0.5,
+        }
+    }
+}
+
+fn loss(color: Color) -> GameResult {
+    match color {
+        Color::White => GameResult::BlackWins,
+        Color::Black => GameResult::WhiteWins,
+    }
+}
+
+// Plays one game from the opening and adjudicates it by the rules: mate,
+// stalemate, the fifty-move rule, repetition and insufficient material. An
+// engine that runs out of time, plays an illegal move or stops responding
+// loses.
+pub fn play_game(
+    white: &mut Engine,
+    black: &mut Engine,
+    opening: &Opening,
+    time_control: TimeControl,
+) -> GameRecord {
+    let mut record = GameRecord {
+        white: white.name.clone(),
+        black: black.name.clone(),
+        fen: opening.fen.clone(),
+        moves: Vec::new(),
+        result: GameResult::Unknown,
+        termination: String::new(),
+    };
+
+    let mut board = Board::from_fen(&opening.fen).unwrap();
+    let mut played = Vec::new();
+    for uci in &opening.moves {
+        let Some(chess_move) = parse_uci_move(&mut board, uci) else {
+            break;
+        };
+        record.moves.push(to_san(&mut board, &chess_move));
+        played.push(uci.clone());
+        board.make_move(&chess_move);
+    }
+
+    for (engine, color) in [(&mut *white, Color::White), (&mut *black, Color::Black)] {
+        if engine.new_game().is_err() {
+            engine.kill();
+            record.result = loss(color);
+            record.termination = format!("{} did not respond", engine.name);
+            return record;
+        }
+    }
+
+    let mut clocks = [time_control.base; 2];
+    let (result, termination) = loop {
+        let side = board.side_to_move;
+        match game_status(&mut board) {
+            GameStatus::Checkmate(Color::White) => break (GameResult::WhiteWins, "checkmate"),
+            GameStatus::Checkmate(Color::Black) => break (GameResult::BlackWins, "checkmate"),
+            GameStatus::Stalemate => break (GameResult::Draw, "stalemate"),
+            GameStatus::FiftyMoveRule => break (GameResult::Draw, "fifty-move rule"),
+            GameStatus::Repetition => break (GameResult::Draw, "repetition"),
+            GameStatus::VariantWin(Color::White) => break (GameResult::WhiteWins, "variant win"),
+            GameStatus::VariantWin(Color::Black) => break (GameResult::BlackWins, "variant win"),
+            GameStatus::VariantDraw => break (GameResult::Draw, "variant draw"),
+            GameStatus::Ongoing if insufficient_material(&board) => {
+                break (GameResult::Draw, "insufficient material")
+            }
+            GameStatus::Ongoing => {}
+        }
+
+        let (engine, clock) = match side {
+            Color::White => (&mut *white, 0),
+            Color::Black => (&mut *black, 1),
+        };
+        let position = if played.is_empty() {
+            format!("position fen {}", opening.fen)
+        } else {
+            format!("position fen {} moves {}", opening.fen, played.join(" "))
+        };
+        let go = format!(
+            "go wtime {} btime {} winc {} binc {}",
+            clocks[0].as_millis(),
+            clocks[1].as_millis(),
+            time_control.increment.as_millis(),
+            time_control.increment.as_millis()
+        );
+
+        let started = Instant::now();
+        let answer = engine.go(&position, &go, clocks[clock] + TIME_MARGIN);
+        let elapsed = started.elapsed();
+
+        let uci = match answer {
+            Ok(uci) => uci,
+            Err(error) if error.kind() == ErrorKind::TimedOut => {
+                break (loss(side), "time forfeit")
+            }
+            Err(_) => break (loss(side), "disconnect"),
+        };
+        if elapsed > clocks[clock] + TIME_MARGIN {
+            break (loss(side), "time forfeit");
+        }
+        clocks[clock] = clocks[clock].saturating_sub(elapsed) + time_control.increment;
+
+        let Some(chess_move) = parse_uci_move(&mut board, &uci) else {
+            break (loss(side), "illegal move");
+        };
+        record.moves.push(to_san(&mut board, &chess_move));
+        played.push(uci);
+        board.make_move(&chess_move);
+    };
+
+    record.result = result;
+    record.termination = termination.to_string();
+    record
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchOptions {
+    pub games: usize,
+    pub concurrency: usize,
+    pub time_control: TimeControl,
+    pub sprt: Option<Sprt>,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            concurrency: 1,
+            time_control: TimeControl {
+                base: Duration::from_secs(10),
+                increment: Duration::from_millis(100),
+            },
+            sprt: None,
+        }
+    }
+}
+
+// The names to show for each engine: its UCI name, numbered where several
+// players share one.
+pub fn player_names(engines: &[EngineConfig]) -> io::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for config in engines {
+        names.push(Engine::start(config)?.name.clone());
+    }
+
+    let unique = names.clone();
+    for (index, name) in names.iter_mut().enumerate() {
+        if unique.iter().filter(|other| *other == name).count() > 1 {
+            name.push_str(&format!(" ({})", index + 1));
+        }
+    }
+    Ok(names)
+}
+
+// A game between two of the players, by index, from one of the openings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScheduledGame {
+    pub white: usize,
+    pub black: usize,
+    pub opening: usize,
+}
+
+// Plays the games on `concurrency` threads, each starting engines as it
+// first needs them, and reports the results in schedule order. Returning
+// false from `on_game` stops new games from starting.
+pub fn play_games(
+    engines: &[EngineConfig],
+    names: &[String],
+    openings: &[Opening],
+    games: &[ScheduledGame],
+    time_control: TimeControl,
+    concurrency: usize,
+    mut on_game: impl FnMut(usize, GameRecord) -> bool,
+) -> io::Result<()> {
+    let default_openings = [Opening::default()];
+    let openings = if openings.is_empty() {
+        &default_openings[..]
+    } else {
+        openings
+    };
+
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..concurrency.max(1) {
+            let sender = sender.clone();
+            let (next, stop) = (&next, &stop);
+
+            scope.spawn(move || {
+                let mut players: Vec<Option<Engine>> = engines.iter().map(|_| None).collect();
+
+                while !stop.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(game) = games.get(index) else {
+                        break;
+                    };
+
+                    for player in [game.white, game.black] {
+                        if players[player]
+                            .as_mut()
+                            .is_some_and(|engine| !engine.is_running())
+                        {
+                            players[player] = None;
+                        }
+                        if players[player].is_none() {
+                            match Engine::start(&engines[player]) {
+                                Ok(mut engine) => {
+                                    engine.name = names[player].clone();
+                                    players[player] = Some(engine);
+                                }
+                                Err(error) => {
+                                    stop.store(true, Ordering::Relaxed);
+                                    let _ = sender.send(Err(error));
+                                    return;
+                                }
+                            }
+                        }
+                    }
+
+                    // Distinct players, so the two borrows don't overlap.
+                    let (low, high) = players.split_at_mut(game.white.max(game.black));
+                    let (white, black) = match (&mut low[game.white.min(game.black)], &mut high[0])
+                    {
+                        (Some(first), Some(second)) if game.white < game.black => (first, second),
+                        (Some(first), Some(second)) => (second, first),
+                        _ => unreachable!(),
+                    };
+                    let opening = &openings[game.opening % openings.len()];
+                    let record = play_game(white, black, opening, time_control);
+
+                    if sender.send(Ok((index, record))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut reported = 0;
+        for message in receiver {
+            let (index, record) = message?;
+            pending.insert(index, record);
+
+            while let Some(record) = pending.remove(&reported) {
+                if !on_game(reported, record) {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                reported += 1;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+// Plays the engines against each other, each opening twice with colours
+// reversed, and reports every game in order with the first engine's running
+// score. Stops early once the SPRT reaches a decision.
+pub fn run_match(
+    engines: &[EngineConfig; 2],
+    openings: &[Opening],
+    options: &MatchOptions,
+    mut on_game: impl FnMut(usize, &GameRecord, &Score),
+) -> io::Result<Score> {
+    let names = player_names(engines)?;
+    let games: Vec<_> = (0..options.games)
+        .map(|index| ScheduledGame {
+            white: index % 2,
+            black: 1 - index % 2,
+            opening: index / 2,
+        })
+        .collect();
+
+    let mut score = Score::default();
+    play_games(
+        engines,
+        &names,
+        openings,
+        &games,
+        options.time_control,
+        options.concurrency,
+        |index, record| {
+            let points = record.white_points();
+            score.add(if index % 2 == 0 { points } else { 1.0 - points });
+            on_game(index, &record, &score);
+
+            options
+                .sprt
+                .is_none_or(|sprt| sprt.test(&score) == SprtResult::Continue)
+        },
+    )?;
+
+    Ok(score)
+}
+
+fn usage() {
+    println!(
+        "Usage: match <engine1> <engine2> [--games N] [--concurrency N] [--tc SECONDS+INC] \
+         [--openings FILE] [--pgn FILE] [--sprt ELO0 ELO1] [--alpha A] [--beta B] \
+         [--option1 NAME=VALUE] [--option2 NAME=VALUE]"
+    );
+}
+
+pub fn run(args: &[String]) {
+    let mut options = MatchOptions::default();
+    let mut engines = [EngineConfig::default(), EngineConfig::default()];
+    let mut openings_path = None;
+    let mut pgn_path = None;
+    let mut sprt = Sprt {
+        elo0: 0.0,
+        elo1: 5.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+    let mut use_sprt = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(String::as_str);
+        let option = |value: Option<&str>| {
+            let (name, value) = value?.split_once('=')?;
+            Some((name.to_string(), value.to_string()))
+        };
+
+        let valid = match arg.as_str() {
+            "--games" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.games = value;
+            }),
+            "--concurrency" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.concurrency = value;
+            }),
+            "--tc" => value().and_then(TimeControl::parse).map(|value| {
+                options.time_control = value;
+            }),
+            "--openings" => value().map(|value| {
+                openings_path = Some(value.to_string());
+            }),
+            "--pgn" => value().map(|value| {
+                pgn_path = Some(value.to_string());
+            }),
+            "--sprt" => {
+                let elo0 = value().and_then(|value| value.parse().ok());
+                let elo1 = value().and_then(|value| value.parse().ok());
+                elo0.zip(elo1).map(|(elo0, elo1)| {
+                    (sprt.elo0, sprt.elo1) = (elo0, elo1);
+                    use_sprt = true;
+                })
+            }
+            "--alpha" => value().and_then(|value| value.parse().ok()).map(|value| {
+                sprt.alpha = value;
+            }),
+            "--beta" => value().and_then(|value| value.parse().ok()).map(|value| {
+                sprt.beta = value;
+            }),
+            "--option1" => option(value()).map(|value| engines[0].options.push(value)),
+            "--option2" => option(value()).map(|value| engines[1].options.push(value)),
+            _ if !arg.starts_with("--") => engines
+                .iter_mut()
+                .find(|engine| engine.command.is_empty())
+                .map(|engine| {
+                    engine.command = arg.split_whitespace().map(str::to_string).collect();
+                }),
+            _ => None,
+        };
+
+        if valid.is_none() {
+            println!("Invalid argument {}", arg);
+            return usage();
+        }
+    }
+
+    if engines.iter().any(|engine| engine.command.is_empty()) {
+        return usage();
+    }
+    if use_sprt {
+        options.sprt = Some(sprt);
+    }
+
+    let openings = match &openings_path {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(text) => parse_openings(&text),
+            Err(error) => return println!("Cannot read {}: {}", path, error),
+        },
+        None => Vec::new(),
+    };
+
+    let mut pgn = match &pgn_path {
+        Some(path) => match File::create(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(error) => return println!("Cannot write {}: {}", path, error),
+        },
+        None => None,
+    };
+
+    let result = run_match(&engines, &openings, &options, |index, record, score| {
+        if let Some(pgn) = &mut pgn {
+            let _ = pgn.write_all(record.to_pgn(index + 1).as_bytes());
+            let _ = pgn.flush();
+        }
+
+        let (elo, margin) = score.elo().unwrap_or_default();
+        let mut line = format!(
+            "Game {}: {} - {} {} ({}) | +{} -{} ={} | Elo {:.1} +/- {:.1}",
+            index + 1,
+            record.white,
+            record.black,
+            record.result.token(),
+            record.termination,
+            score.wins,
+            score.losses,
+            score.draws,
+            elo,
+            margin
+        );
+        if let Some(sprt) = options.sprt {
+            let (lower, upper) = sprt.bounds();
+            line.push_str(&format!(
+                " | LLR {:.2} ({:.2}, {:.2})",
+                score.llr(sprt.elo0, sprt.elo1),
+                lower,
+                upper
+            ));
+        }
+        println!("{}", line);
+    });
+
+    match result {
+        Ok(score) => {
+            println!(
+                "Finished {} games: +{} -{} ={}",
+                score.games(),
+                score.wins,
+                score.losses,
+                score.draws
+            );
+            if let Some(sprt) = options.sprt {
+                match sprt.test(&score) {
+                    SprtResult::AcceptH1 => println!("SPRT: H1 accepted"),
+                    SprtResult::AcceptH0 => println!("SPRT: H0 accepted"),
+                    SprtResult::Continue => println!("SPRT: no decision"),
+                }
+            }
+        }
+        Err(error) => println!("Cannot run the match: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_controls() {
+        assert_eq!(
+            TimeControl::parse("10+0.1"),
+            Some(TimeControl {
+                base: Duration::from_secs(10),
+                increment: Duration::from_millis(100),
+            })
+        );
+        assert_eq!(TimeControl::parse("60").unwrap().increment, Duration::ZERO);
+        assert_eq!(TimeControl::parse("-1+0"), None);
+        assert_eq!(TimeControl::parse("fast"), None);
+    }
+
+    #[test]
+    fn openings_from_pgn_and_epd() {
+        let pgn = parse_openings("[Event \"?\"]\n\n1. e4 c5 2. Nf3 *\n\n1. d4 d5 *\n");
+        assert_eq!(pgn.len(), 2);
+        assert_eq!(pgn[0].fen, STARTING_FEN_STRING);
+        assert_eq!(pgn[0].moves, ["e2e4", "c7c5", "g1f3"]);
+
+        let epd = parse_openings(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - id \"e4\";\n\
+             4k3/8/8/8/8/8/8/4K3 w - - 3 20\nnot a position\n",
+        );
+        assert_eq!(epd.len(), 2);
+        assert_eq!(
+            epd[0].fen,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
+        assert_eq!(epd[1].fen, "4k3/8/8/8/8/8/8/4K3 w - - 3 20");
+
+        let epd = parse_openings("1k1r4/8/8/8/8/8/8/4K3 w - -\n1r3rk1/8/8/8/8/8/8/4K3 b - -\n");
+        assert_eq!(epd.len(), 2);
+        assert_eq!(epd[0].fen, "1k1r4/8/8/8/8/8/8/4K3 w - - 0 1");
+
+        let pgn = parse_openings("1. e4 e5 *\n");
+        assert_eq!(pgn[0].moves, ["e2e4", "e7e5"]);
+    }
+
+    // A stand-in engine: a shell script reading UCI commands.
+    #[cfg(unix)]
+    fn script_engine(script: &str) -> Engine {
+        let config = EngineConfig {
+            command: vec!["sh".into(), "-c".into(), script.into()],
+            options: Vec::new(),
+        };
+        Engine::start(&config).unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn late_best_moves_are_drained() {
+        let mut engine = script_engine(
+            "while read -r line; do case \"$line\" in \
+             uci) echo uciok ;; isready) echo readyok ;; \
+             go*) (sleep 0.2; echo bestmove e2e4) & ;; esac; done",
+        );
+
+        let error = engine
+            .go("position startpos", "go", Duration::from_millis(20))
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+        assert!(engine.is_running());
+
+        engine.new_game().unwrap();
+        thread::sleep(Duration::from_millis(300));
+        assert!(engine.lines.try_recv().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn engines_that_stop_responding_lose() {
+        let mut white = script_engine(
+            "while read -r line; do case \"$line\" in \
+             uci) echo uciok ;; isready) echo readyok ;; esac; done",
+        );
+        let mut black = script_engine(
+            "while read -r line; do case \"$line\" in \
+             uci) echo uciok ;; isready) echo readyok ;; ucinewgame) exit ;; esac; done",
+        );
+
+        let time_control = TimeControl::parse("1").unwrap();
+        let record = play_game(&mut white, &mut black, &Opening::default(), time_control);
+        assert_eq!(record.result, GameResult::WhiteWins);
+        assert_eq!(record.white_points(), 1.0);
+        assert!(!black.is_running());
+    }
+
+    #[test]
+    fn game_records_as_pgn() {
+        let record = GameRecord {
+            white: "A".into(),
+            black: "B".into(),
+            fen: "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".into(),
+            moves: vec!["e5".into(), "Nf3".into(), "Nc6".into()],
+            result: GameResult::Draw,
+            termination: "repetition".into(),
+        };
+
+        let pgn = record.to_pgn(3);
+        assert!(pgn.contains("[Round \"3\"]\n"));
+        assert!(
+            pgn.contains("[FEN \"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\"]")
+        );
+        assert!(pgn.ends_with("\n\n1... e5 2. Nf3 Nc6 1/2-1/2\n\n"));
+
+        let games = parse_pgn(&pgn);
+        assert_eq!(games[0].result(), GameResult::Draw);
+        assert_eq!(games[0].moves, record.moves);
+    }
+
+    #[test]
+    fn draws_by_insufficient_material() {
+        let draw = |fen| insufficient_material(&Board::from_fen(fen).unwrap());
+
+        assert!(draw("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(draw("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(!draw("4k3/8/8/8/8/8/8/3BKN2 w - - 0 1"));
+        assert!(!draw("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Instant,
+};
+
+use crate::{
+    board::{Board, STARTING_FEN_STRING},
+    search::{SearchLimits, SearchOptions, Searcher},
+};
+
+pub const BENCH_POSITIONS: [&str; 8] = [
+    STARTING_FEN_STRING,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "2r3k1/pp3ppp/2n1b3/3p4/3P4/2N1B3/PP3PPP/2R3K1 w - - 0 1",
+];
+
+// Total nodes searched over the given positions at a fixed depth.
+pub fn bench_nodes(positions: &[&str], depth: u32, options: SearchOptions) -> u64 {
+    positions
+        .iter()
+        .map(|fen| {
+            let board = Board::from_fen(fen).unwrap();
+            let limits = SearchLimits {
+                depth: Some(depth),
+                ..Default::default()
+            };
+
+            let mut searcher =
+                Searcher::new(board, limits, options, Arc::new(AtomicBool::new(false)));
+            searcher.search(|_| {});
+            searcher.nodes()
+        })
+        .sum()
+}
+
+pub fn run(depth: u32) {
+    let configurations = [
+        (
+            "unordered",
+            SearchOptions {
+                move_ordering: false,
+                ..SearchOptions::without_pruning()
+            },
+        ),
+        ("ordered", SearchOptions::without_pruning()),
+        ("pruned", SearchOptions::default()),
+    ];
+
+    let mut results = Vec::new();
+    for (name, options) in configurations {
+        let start = Instant::now();
+        let mut total = 0;
+        for fen in BENCH_POSITIONS {
+            let nodes = bench_nodes(&[fen], depth, options);
+            println!("{:<10} {:>12} {}", name, nodes, fen);
+            total += nodes;
+        }
+
+        let millis = start.elapsed().as_millis().max(1) as u64;
+        println!(
+            "{:<10} {:>12} nodes {:>8} ms {:>10} nps",
+            name,
+            total,
+            millis,
+            total * 1000 / millis
+        );
+        results.push(total);
+    }
+
+    println!(
+        "Move ordering searched {:.1}% of the unordered node count at depth {}",
+        results[1] as f64 * 100.0 / results[0] as f64,
+        depth
+    );
+    println!(
+        "Pruning searched {:.1}% of the ordered node count at depth {}",
+        results[2] as f64 * 100.0 / results[1] as f64,
+        depth
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_ordering_reduces_node_count() {
+        let positions = [BENCH_POSITIONS[0], BENCH_POSITIONS[6], BENCH_POSITIONS[7]];
+
+        let unordered = bench_nodes(
+            &positions,
+            3,
+            SearchOptions {
+                move_ordering: false,
+                ..SearchOptions::without_pruning()
+            },
+        );
+        let ordered = bench_nodes(&positions, 3, SearchOptions::without_pruning());
+
+        assert!(ordered < unordered);
+    }
+}
@@ -27,6 +27,10 @@ impl Bitset {
         self.get_bit(index) != 0
     }
 
+    pub fn count(&self) -> u32 {
+        self.0.count_ones()
+    }
+
     pub fn set_bit_indices(&self) -> Vec<u8> {
         let mut indices = Vec::new();
         for index in 0..64 {
@@ -1,19 +1,114 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 use crate::{
     bitset::Bitset,
-    movegen::{valid_moves, Move, MoveType},
+    index_to_pos,
+    movegen::{Move, MoveType},
+    nnue::{Accumulators, Network},
     piece::{Color, Piece},
+    pos_to_index,
+    variant::{Crazyhouse, Standard, ThreeCheck, Variant},
+    zobrist::{check_key, piece_key, pocket_key, CASTLING_KEYS, EN_PASSANT_KEYS, SIDE_KEY},
 };
 
 pub const STARTING_FEN_STRING: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 pub const CASTLING_FLAGS: [char; 4] = ['K', 'Q', 'k', 'q'];
 
+// Rook starting squares of standard chess, in the same order as the castling
+// flags. Chess960 positions keep their own in the board.
+const CASTLING_ROOKS: [(u8, u8); 4] = [(7, 7), (7, 0), (0, 7), (0, 0)];
+
+// Knight placements on the five squares left after the bishops and the queen,
+// in Scharnagl's numbering of the Chess960 start positions.
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// The FEN of Chess960 start position 0..960, numbered as in Scharnagl's
+// scheme so that 518 is the standard start position.
+pub fn chess960_fen(index: u16) -> Option<String> {
+    if index >= 960 {
+        return None;
+    }
+
+    let mut rank = [None; 8];
+    let mut n = index as usize;
+
+    rank[2 * (n % 4) + 1] = Some('B');
+    n /= 4;
+    rank[2 * (n % 4)] = Some('B');
+    n /= 4;
+
+    let mut place = |piece: char, nth: usize| {
+        let file = (0..8)
+            .filter(|file| rank[*file].is_none())
+            .nth(nth)
+            .unwrap();
+        rank[file] = Some(piece);
+    };
+    place('Q', n % 6);
+    n /= 6;
+
+    // Both knights are picked from the same five squares, so the second
+    // index shifts once the first is filled.
+    let (first, second) = KNIGHT_PLACEMENTS[n];
+    place('N', first);
+    place('N', second - 1);
+    for piece in ['R', 'K', 'R'] {
+        place(piece, 0);
+    }
+
+    let white: String = rank.iter().map(|piece| piece.unwrap()).collect();
+    Some(format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        white.to_ascii_lowercase(),
+        white
+    ))
+}
+
+#[derive(Clone)]
+struct BoardState {
+    can_castle: [bool; 4],
+    en_passant: Option<(u8, u8)>,
+    halfmove_clock: u32,
+    hash: u64,
+    pockets: [[u8; 5]; 2],
+    promoted: Bitset,
+    checks: [u8; 2],
+    // Pieces removed by an atomic capture, the capturer included.
+    exploded: Vec<(u8, Piece)>,
+}
+
+#[derive(Clone)]
 pub struct Board {
     pub pieces: [Bitset; 12],
     pub occupied: [Bitset; 3],
     pub side_to_move: Color,
     pub can_castle: [bool; 4],
+    pub castling_rooks: [(u8, u8); 4],
+    pub variant: &'static dyn Variant,
+    // Pieces in hand for drop variants, by color (white first) and kind, and
+    // the squares of promoted pieces, which go back to being pawns when
+    // captured.
+    pub pockets: [[u8; 5]; 2],
+    pub promoted: Bitset,
+    // Checks given by each color, for three-check.
+    pub checks: [u8; 2],
+    pub en_passant: Option<(u8, u8)>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub hash: u64,
+    history: Vec<BoardState>,
+    nnue: Option<Accumulators>,
 }
 
 impl Board {
@@ -23,12 +118,27 @@ impl Board {
             occupied: [const { Bitset::new(0) }; 3],
             side_to_move: Color::White,
             can_castle: [true; 4],
+            castling_rooks: CASTLING_ROOKS,
+            variant: &Standard,
+            pockets: [[0; 5]; 2],
+            promoted: Bitset::new(0),
+            checks: [0; 2],
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            hash: 0,
+            history: Vec::new(),
+            nnue: None,
         }
     }
 
     pub fn get(&self, rank: u8, file: u8) -> Option<Piece> {
+        let square = rank * 8 + file;
+        if !self.occupied[0].is_bit_set(square) {
+            return None;
+        }
+
         for (index, bitset) in self.pieces.iter().enumerate() {
-            let square = rank * 8 + file;
             if bitset.is_bit_set(square) {
                 let piece = Piece::from_index(index);
                 return piece;
@@ -52,6 +162,11 @@ impl Board {
         };
 
         occupied.set_bit(square);
+
+        self.hash ^= piece_key(piece.index(), square);
+        if let Some(nnue) = &mut self.nnue {
+            nnue.add(piece, square);
+        }
     }
 
     pub fn clear(&mut self, rank: u8, file: u8, piece: Piece) {
@@ -68,25 +183,80 @@ impl Board {
         };
 
         occupied.clear_bit(square);
+
+        self.hash ^= piece_key(piece.index(), square);
+        if let Some(nnue) = &mut self.nnue {
+            nnue.remove(piece, square);
+        }
+    }
+
+    // Evaluates with the network from now on, or with the classic
+    // evaluation for None.
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.nnue = network.map(|network| Accumulators::new(network, self));
+    }
+
+    pub fn accumulators(&self) -> Option<&Accumulators> {
+        self.nnue.as_ref()
+    }
+
+    pub fn king_square(&self, color: Color) -> Option<(u8, u8)> {
+        let king = Piece::from_kind(5, color).unwrap();
+        self.pieces[king.index()]
+            .set_bit_indices()
+            .first()
+            .map(|square| (square / 8, square % 8))
     }
 
-    // TODO: Support the rest of the FEN string.
     pub fn from_fen(fen: &str) -> Option<Self> {
         let mut board = Board::new();
 
-        let fen_config = fen.split(" ").collect::<Vec<_>>();
-        if fen_config.len() != 6 {
+        let mut fen_config = fen.split_whitespace().collect::<Vec<_>>();
+
+        // Three-check FENs end with the checks each side has given, as in
+        // "+1+0".
+        if let Some(checks) = fen_config.last().and_then(|last| last.strip_prefix('+')) {
+            let (white, black) = checks.split_once('+')?;
+            board.checks = [white.parse().ok()?, black.parse().ok()?];
+            board.variant = &ThreeCheck;
+            fen_config.pop();
+        }
+
+        if !(4..=6).contains(&fen_config.len()) {
             return None;
         }
 
-        let position = fen_config[0];
+        // Crazyhouse FENs carry the pieces in hand after the placement, as in
+        // "...RNBQKBNR[Qp]".
+        let mut position = fen_config[0];
+        if let Some((placement, pocket)) = position.split_once('[') {
+            board.variant = &Crazyhouse;
+            for c in pocket.strip_suffix(']')?.chars() {
+                let piece = Piece::from_char(c).filter(|piece| !piece.is_king())?;
+                board.pockets[piece.color().index() - 1][piece.kind()] += 1;
+            }
+            position = placement;
+        }
 
         let rows = position.split("/");
         for (rank, row) in rows.enumerate() {
             let mut file = 0;
             for c in row.chars() {
+                // A tilde marks the piece before it as promoted.
+                if c == '~' && file > 0 {
+                    board.promoted.set_bit(rank as u8 * 8 + file - 1);
+                    continue;
+                }
+
+                if rank >= 8 || file >= 8 {
+                    return None;
+                }
+
                 if c.is_ascii_alphabetic() {
+                    // A pawn can't stand on the rank it promotes on.
                     match Piece::from_char(c) {
+                        Some(Piece::WhitePawn) if rank == 0 => return None,
+                        Some(Piece::BlackPawn) if rank == 7 => return None,
                         Some(piece) => board.set(rank as u8, file, piece),
                         None => return None,
                     }
@@ -108,96 +278,535 @@ impl Board {
         board.side_to_move = match side_to_move {
             "w" => Color::White,
             "b" => Color::Black,
-            _ => panic!("Invalid active color"),
+            _ => return None,
         };
 
         board.can_castle = [false; 4];
         let can_castle = fen_config[2];
         for flag in can_castle.chars() {
-            match flag {
-                '-' => break,
-                'K' => board.can_castle[0] = true,
-                'Q' => board.can_castle[1] = true,
-                'k' => board.can_castle[2] = true,
-                'q' => board.can_castle[3] = true,
-                _ => panic!("Invalid castling flags"),
+            if flag == '-' {
+                break;
             }
+
+            let color = if flag.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let rank = match color {
+                Color::White => 7,
+                Color::Black => 0,
+            };
+            let king_file = board
+                .king_square(color)
+                .filter(|square| square.0 == rank)
+                .map(|square| square.1);
+
+            // X-FEN names the side and means its outermost rook, Shredder-FEN
+            // names the rook's file.
+            let rook_file = match flag.to_ascii_lowercase() {
+                'k' => board.outermost_rook(color, king_file, true).unwrap_or(7),
+                'q' => board.outermost_rook(color, king_file, false).unwrap_or(0),
+                'a'..='h' => flag.to_ascii_lowercase() as u8 - b'a',
+                _ => return None,
+            };
+            let king_side = king_file.map_or(rook_file > 4, |file| rook_file > file);
+
+            let index = 2 * color.index() - 1 - king_side as usize;
+            board.can_castle[index] = true;
+            board.castling_rooks[index] = (rank, rook_file);
+        }
+
+        let en_passant = fen_config[3];
+        board.en_passant = match en_passant {
+            "-" => None,
+            _ => Some(pos_to_index(en_passant)?),
+        };
+
+        if let Some(halfmove_clock) = fen_config.get(4) {
+            board.halfmove_clock = halfmove_clock.parse().ok()?;
         }
 
+        if let Some(fullmove_number) = fen_config.get(5) {
+            board.fullmove_number = fullmove_number.parse().ok()?;
+        }
+
+        board.hash ^= board.state_key();
+
+        Some(board)
+    }
+
+    // A position played under the given rules, which must have a use for any
+    // pocket or check counts the FEN carries.
+    pub fn from_variant_fen(fen: &str, variant: &'static dyn Variant) -> Option<Self> {
+        let mut board = Board::from_fen(fen)?;
+        if (board.variant.has_drops() && !variant.has_drops())
+            || (board.variant.counts_checks() && !variant.counts_checks())
+        {
+            return None;
+        }
+
+        board.variant = variant;
         Some(board)
     }
 
+    pub fn to_fen(&self) -> String {
+        let mut position = String::new();
+        for rank in 0..8 {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.get(rank, file) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            position.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        position.push(piece.simple_char());
+                        if self.promoted.is_bit_set(rank * 8 + file) {
+                            position.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                position.push_str(&empty.to_string());
+            }
+            if rank < 7 {
+                position.push('/');
+            }
+        }
+
+        if self.variant.has_drops() {
+            position.push('[');
+            for color in [Color::White, Color::Black] {
+                for kind in [4, 3, 2, 1, 0] {
+                    let piece = Piece::from_kind(kind, color).unwrap();
+                    for _ in 0..self.pockets[color.index() - 1][kind] {
+                        position.push(piece.simple_char());
+                    }
+                }
+            }
+            position.push(']');
+        }
+
+        let side_to_move = match self.side_to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+
+        let can_castle = self.castling_field();
+
+        let en_passant = match self.en_passant {
+            Some(square) => index_to_pos(square).unwrap(),
+            None => "-".to_string(),
+        };
+
+        let mut fen = format!(
+            "{} {} {} {} {} {}",
+            position,
+            side_to_move,
+            can_castle,
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_number
+        );
+        if self.variant.counts_checks() {
+            fen.push_str(&format!(" +{}+{}", self.checks[0], self.checks[1]));
+        }
+        fen
+    }
+
+    // The rook furthest from the king on one side of it, on the color's back
+    // rank.
+    fn outermost_rook(&self, color: Color, king_file: Option<u8>, king_side: bool) -> Option<u8> {
+        let rank = match color {
+            Color::White => 7,
+            Color::Black => 0,
+        };
+        let rook = Piece::from_kind(3, color).unwrap();
+        let king_file = king_file?;
+
+        let mut files: Vec<u8> = if king_side {
+            (king_file + 1..8).rev().collect()
+        } else {
+            (0..king_file).collect()
+        };
+        files.retain(|file| self.get(rank, *file) == Some(rook));
+        files.first().copied()
+    }
+
+    // The castling field of the FEN: KQkq when the castling rooks are the
+    // outermost ones, as in X-FEN, and the rook files otherwise.
+    pub fn castling_field(&self) -> String {
+        let mut field = String::new();
+        for (index, flag) in CASTLING_FLAGS.iter().enumerate() {
+            if !self.can_castle[index] {
+                continue;
+            }
+
+            let color = if index < 2 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king_file = self.king_square(color).map(|square| square.1);
+            let (_, rook_file) = self.castling_rooks[index];
+            if self.outermost_rook(color, king_file, index % 2 == 0) == Some(rook_file) {
+                field.push(*flag);
+            } else {
+                let file = (b'a' + rook_file) as char;
+                field.push(match color {
+                    Color::White => file.to_ascii_uppercase(),
+                    Color::Black => file,
+                });
+            }
+        }
+
+        if field.is_empty() {
+            field.push('-');
+        }
+        field
+    }
+
+    // Hash contribution of everything except piece placement.
+    fn state_key(&self) -> u64 {
+        let mut key = 0;
+
+        if self.side_to_move == Color::Black {
+            key ^= SIDE_KEY;
+        }
+
+        for (index, flag) in self.can_castle.iter().enumerate() {
+            if *flag {
+                key ^= CASTLING_KEYS[index];
+            }
+        }
+
+        if let Some((_, file)) = self.en_passant {
+            key ^= EN_PASSANT_KEYS[file as usize];
+        }
+
+        for (color_index, pocket) in self.pockets.iter().enumerate() {
+            for (kind, count) in pocket.iter().enumerate() {
+                if *count > 0 {
+                    key ^= pocket_key(color_index, kind, *count);
+                }
+            }
+        }
+
+        for (color_index, count) in self.checks.iter().enumerate() {
+            if *count > 0 {
+                key ^= check_key(color_index, *count);
+            }
+        }
+
+        key
+    }
+
+    fn push_state(&mut self) {
+        self.history.push(BoardState {
+            can_castle: self.can_castle,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+            pockets: self.pockets,
+            promoted: self.promoted,
+            checks: self.checks,
+            exploded: Vec::new(),
+        });
+    }
+
+    fn pop_state(&mut self) {
+        let state = self.history.pop().unwrap();
+        self.can_castle = state.can_castle;
+        self.en_passant = state.en_passant;
+        self.halfmove_clock = state.halfmove_clock;
+        self.hash = state.hash;
+        self.pockets = state.pockets;
+        self.promoted = state.promoted;
+        self.checks = state.checks;
+    }
+
+    // Where the rook starts and ends when the king castles to the given square,
+    // which is on the g file for castling king side and the c file otherwise.
+    pub fn castling_rook_squares(&self, king_to: (u8, u8)) -> ((u8, u8), (u8, u8)) {
+        let king_side = king_to.1 == 6;
+        let index = match self.side_to_move {
+            Color::White => 1 - king_side as usize,
+            Color::Black => 3 - king_side as usize,
+        };
+        let rook_file = if king_side { 5 } else { 3 };
+        (self.castling_rooks[index], (king_to.0, rook_file))
+    }
+
+    // Puts a captured piece in the mover's pocket, as a pawn if it had been
+    // promoted, and keeps track of where promoted pieces are.
+    pub fn update_pockets(&mut self, chess_move: &Move) {
+        let from = chess_move.from.0 * 8 + chess_move.from.1;
+        let to = chess_move.to.0 * 8 + chess_move.to.1;
+
+        // Exchanges played out by SEE can take a king; it has no pocket.
+        if let Some(captured) = chess_move.captured().filter(|piece| !piece.is_king()) {
+            let kind = if self.promoted.is_bit_set(to) {
+                0
+            } else {
+                captured.kind()
+            };
+            self.pockets[self.side_to_move.index() - 1][kind] += 1;
+        }
+
+        let promoted = chess_move.promotion().is_some()
+            || (chess_move.move_type != MoveType::Drop && self.promoted.is_bit_set(from));
+        self.promoted.clear_bit(from);
+        self.promoted.clear_bit(to);
+        if promoted {
+            self.promoted.set_bit(to);
+        }
+    }
+
+    // Removes the piece on the square and every piece but pawns around it,
+    // remembering them for unmake_move. Rooks and kings caught in it take
+    // their castling rights with them.
+    pub fn explode(&mut self, center: (u8, u8)) {
+        let mut exploded = Vec::new();
+
+        for rank in center.0.saturating_sub(1)..=(center.0 + 1).min(7) {
+            for file in center.1.saturating_sub(1)..=(center.1 + 1).min(7) {
+                let Some(piece) = self.get(rank, file) else {
+                    continue;
+                };
+                if piece.is_pawn() && (rank, file) != center {
+                    continue;
+                }
+
+                self.clear(rank, file, piece);
+                exploded.push((rank * 8 + file, piece));
+
+                for (index, rook_square) in self.castling_rooks.iter().enumerate() {
+                    let color_index = if index < 2 { 1 } else { 2 };
+                    if *rook_square == (rank, file)
+                        || (piece.is_king() && piece.color().index() == color_index)
+                    {
+                        self.can_castle[index] = false;
+                    }
+                }
+            }
+        }
+
+        self.history.last_mut().unwrap().exploded = exploded;
+    }
+
     pub fn make_move(&mut self, chess_move: &Move) {
         let Move {
             from,
             to,
             piece,
             move_type,
-        } = chess_move;
+        } = *chess_move;
 
-        let valid_moves = valid_moves(self, from);
-        if !valid_moves.is_bit_set(to.0 * 8 + to.1) {
-            println!("Not a valid move!");
-            return;
+        self.push_state();
+        self.hash ^= self.state_key();
+        if let Some(nnue) = &mut self.nnue {
+            nnue.push();
         }
 
-        self.clear(from.0, from.1, *piece);
-        self.set(to.0, to.1, *piece);
+        if move_type != MoveType::Drop {
+            self.clear(from.0, from.1, piece);
+        }
 
         match move_type {
-            MoveType::Normal => {
-                if *piece == Piece::WhiteKing {
-                    self.can_castle[0] = false;
-                    self.can_castle[1] = false;
-                } else if *piece == Piece::BlackKing {
-                    self.can_castle[2] = false;
-                    self.can_castle[3] = false;
-                } else if *piece == Piece::WhiteRook {
-                    if from.1 == 7 {
-                        self.can_castle[0] = false;
-                    } else if from.1 == 0 {
-                        self.can_castle[1] = false;
-                    }
-                } else if *piece == Piece::BlackRook {
-                    if from.1 == 7 {
-                        self.can_castle[2] = false;
-                    } else if from.1 == 0 {
-                        self.can_castle[3] = false;
-                    }
+            MoveType::Normal => self.set(to.0, to.1, piece),
+
+            MoveType::Capture(capture) => {
+                self.clear(to.0, to.1, capture);
+                self.set(to.0, to.1, piece);
+            }
+
+            MoveType::EnPassant => {
+                let pawn = Piece::from_kind(0, self.side_to_move.opposite()).unwrap();
+                self.clear(from.0, to.1, pawn);
+                self.set(to.0, to.1, piece);
+            }
+
+            MoveType::Promotion(promotion, capture) => {
+                if let Some(capture) = capture {
+                    self.clear(to.0, to.1, capture);
                 }
+                self.set(to.0, to.1, promotion);
+            }
+
+            // The king and rook may land on each other's squares in Chess960,
+            // so both leave before either arrives.
+            MoveType::Castling => {
+                let (rook_from, rook_to) = self.castling_rook_squares(to);
+                let rook = Piece::from_kind(3, self.side_to_move).unwrap();
+                self.clear(rook_from.0, rook_from.1, rook);
+                self.set(rook_to.0, rook_to.1, rook);
+                self.set(to.0, to.1, piece);
             }
 
+            MoveType::Drop => {
+                self.pockets[self.side_to_move.index() - 1][piece.kind()] -= 1;
+                self.set(to.0, to.1, piece);
+            }
+        }
+
+        let variant = self.variant;
+        variant.after_move(self, chess_move);
+
+        if piece.is_king() {
+            let (king_castle_index, queen_castle_index) = (
+                2 * self.side_to_move.index() - 2,
+                2 * self.side_to_move.index() - 1,
+            );
+            self.can_castle[king_castle_index] = false;
+            self.can_castle[queen_castle_index] = false;
+        }
+
+        for (index, rook_square) in self.castling_rooks.iter().enumerate() {
+            if from == *rook_square || to == *rook_square {
+                self.can_castle[index] = false;
+            }
+        }
+
+        // Only a push from the usual second rank can be taken en passant, not
+        // the horde's from the first.
+        self.en_passant =
+            if piece.is_pawn() && from.0.abs_diff(to.0) == 2 && [1, 6].contains(&from.0) {
+                Some(((from.0 + to.0) / 2, from.1))
+            } else {
+                None
+            };
+
+        if piece.is_pawn() || chess_move.is_capture() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+
+        if self.side_to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
+
+        self.side_to_move = self.side_to_move.opposite();
+        self.hash ^= self.state_key();
+    }
+
+    pub fn unmake_move(&mut self, chess_move: &Move) {
+        let Move {
+            from,
+            to,
+            piece,
+            move_type,
+        } = *chess_move;
+
+        self.side_to_move = self.side_to_move.opposite();
+        if self.side_to_move == Color::Black {
+            self.fullmove_number -= 1;
+        }
+
+        // The previous accumulators are still on the stack, so restoring the
+        // pieces mustn't touch them.
+        let mut nnue = self.nnue.take();
+        if let Some(nnue) = &mut nnue {
+            nnue.pop();
+        }
+
+        let exploded = std::mem::take(&mut self.history.last_mut().unwrap().exploded);
+        for (square, piece) in exploded {
+            self.set(square / 8, square % 8, piece);
+        }
+
+        match move_type {
+            MoveType::Normal => self.clear(to.0, to.1, piece),
+
             MoveType::Capture(capture) => {
-                self.clear(to.0, to.1, *capture);
+                self.clear(to.0, to.1, piece);
+                self.set(to.0, to.1, capture);
             }
 
-            MoveType::Castling => {
-                let rook = if self.side_to_move == Color::White {
-                    Piece::WhiteRook
-                } else {
-                    Piece::BlackRook
-                };
+            MoveType::EnPassant => {
+                let pawn = Piece::from_kind(0, self.side_to_move.opposite()).unwrap();
+                self.clear(to.0, to.1, piece);
+                self.set(from.0, to.1, pawn);
+            }
 
-                if to.1 == 6 {
-                    self.clear(to.0, 7, rook);
-                    self.set(to.0, 5, rook);
-                } else if to.1 == 2 {
-                    self.clear(to.0, 0, rook);
-                    self.set(to.0, 3, rook);
-                } else {
-                    panic!()
+            MoveType::Promotion(promotion, capture) => {
+                self.clear(to.0, to.1, promotion);
+                if let Some(capture) = capture {
+                    self.set(to.0, to.1, capture);
                 }
+            }
 
-                let (king_castle_index, queen_castle_index) = (
-                    2 * self.side_to_move.index() - 2,
-                    2 * self.side_to_move.index() - 1,
-                );
-                self.can_castle[king_castle_index] = false;
-                self.can_castle[queen_castle_index] = false;
+            MoveType::Castling => {
+                let (rook_from, rook_to) = self.castling_rook_squares(to);
+                let rook = Piece::from_kind(3, self.side_to_move).unwrap();
+                self.clear(to.0, to.1, piece);
+                self.clear(rook_to.0, rook_to.1, rook);
+                self.set(rook_from.0, rook_from.1, rook);
             }
+
+            MoveType::Drop => self.clear(to.0, to.1, piece),
         }
 
+        if move_type != MoveType::Drop {
+            self.set(from.0, from.1, piece);
+        }
+        self.nnue = nnue;
+
+        self.pop_state();
+    }
+
+    // Passes the turn without moving, for null-move pruning.
+    pub fn make_null_move(&mut self) {
+        self.push_state();
+        self.hash ^= self.state_key();
+
+        self.en_passant = None;
+        self.halfmove_clock += 1;
+        self.side_to_move = self.side_to_move.opposite();
+        self.hash ^= self.state_key();
+    }
+
+    pub fn unmake_null_move(&mut self) {
         self.side_to_move = self.side_to_move.opposite();
+
+        self.pop_state();
+    }
+
+    // Whether the side has anything besides pawns and its king.
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        (1..5).any(|kind| {
+            let piece = Piece::from_kind(kind, color).unwrap();
+            self.pieces[piece.index()] != Bitset::new(0)
+        })
+    }
+
+    pub fn is_repetition(&self) -> bool {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|state| state.hash == self.hash)
+    }
+
+    pub fn repetition_count(&self) -> usize {
+        self.history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .filter(|state| state.hash == self.hash)
+            .count()
+    }
+
+    pub fn is_fifty_move_draw(&self) -> bool {
+        self.halfmove_clock >= 100
     }
 }
 
@@ -329,4 +938,109 @@ mod tests {
     fn invalid_fen_too_many_rows() {
         Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR/8 w KQkq - 0 1").unwrap();
     }
+
+    #[test]
+    fn null_move_round_trip() {
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2";
+        let mut board = Board::from_fen(fen).unwrap();
+        let hash = board.hash;
+
+        board.make_null_move();
+        assert_eq!(board.side_to_move, Color::Black);
+        assert_eq!(board.en_passant, None);
+        assert_eq!(
+            board.hash,
+            Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 1 2")
+                .unwrap()
+                .hash
+        );
+
+        board.unmake_null_move();
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(board.hash, hash);
+    }
+
+    #[test]
+    fn non_pawn_material() {
+        let board = Board::from_fen("4k3/pppp4/8/8/8/8/4P3/3NK3 w - - 0 1").unwrap();
+
+        assert!(board.has_non_pawn_material(Color::White));
+        assert!(!board.has_non_pawn_material(Color::Black));
+    }
+
+    #[test]
+    fn board_can_be_sent_to_search_threads() {
+        fn assert_clone_send<T: Clone + Send>() {}
+        assert_clone_send::<Board>();
+    }
+
+    #[test]
+    fn chess960_start_positions() {
+        assert_eq!(chess960_fen(518).unwrap(), STARTING_FEN_STRING);
+        assert_eq!(
+            chess960_fen(0).unwrap(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+        assert_eq!(chess960_fen(960), None);
+
+        let mut positions: Vec<_> = (0..960).map(|index| chess960_fen(index).unwrap()).collect();
+        positions.sort();
+        positions.dedup();
+        assert_eq!(positions.len(), 960);
+
+        for fen in positions {
+            let board = Board::from_fen(&fen).unwrap();
+            assert_eq!(board.to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn shredder_and_x_fen_castling() {
+        // The outermost rooks keep the X-FEN letters.
+        let board = Board::from_fen("1r1k2r1/8/8/8/8/8/8/1R1K2R1 w GBgb - 0 1").unwrap();
+        assert_eq!(board.castling_rooks, [(7, 6), (7, 1), (0, 6), (0, 1)]);
+        assert_eq!(board.castling_field(), "KQkq");
+        assert!(Board::from_fen("1r1k2r1/8/8/8/8/8/8/1R1K2R1 w KZ - 0 1").is_none());
+
+        let x_fen = Board::from_fen("1r1k2r1/8/8/8/8/8/8/1R1K2R1 w KQkq - 0 1").unwrap();
+        assert_eq!(x_fen.castling_rooks, board.castling_rooks);
+        assert_eq!(x_fen.hash, board.hash);
+
+        // An inner rook has to be named by its file.
+        let board = Board::from_fen("r2k1rr1/8/8/8/8/8/8/R2K1RR1 w FAf - 0 1").unwrap();
+        assert_eq!(board.castling_rooks[0], (7, 5));
+        assert_eq!(board.castling_rooks[2], (0, 5));
+        assert_eq!(board.to_fen(), "r2k1rr1/8/8/8/8/8/8/R2K1RR1 w FQf - 0 1");
+        assert_eq!(
+            Board::from_fen(&board.to_fen()).unwrap().to_fen(),
+            board.to_fen()
+        );
+    }
+
+    #[test]
+    fn crazyhouse_pockets_and_promotions() {
+        let fen = "4k1r1/8/8/8/8/8/8/4K1Q~1[NPp] b - - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.variant.name(), "crazyhouse");
+        assert!(Board::from_variant_fen(fen, &Standard).is_none());
+        assert_eq!(board.pockets, [[1, 1, 0, 0, 0], [1, 0, 0, 0, 0]]);
+        assert!(board.promoted.is_bit_set(62));
+        assert_eq!(board.to_fen(), fen);
+
+        // The promoted queen goes back to being a pawn in Black's pocket.
+        let capture = Move {
+            from: (0, 6),
+            to: (7, 6),
+            piece: Piece::BlackRook,
+            move_type: MoveType::Capture(Piece::WhiteQueen),
+        };
+        board.make_move(&capture);
+        assert_eq!(board.pockets[1], [2, 0, 0, 0, 0]);
+        assert!(!board.promoted.is_bit_set(62));
+        assert_eq!(board.hash, Board::from_fen(&board.to_fen()).unwrap().hash);
+
+        board.unmake_move(&capture);
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(board.hash, Board::from_fen(fen).unwrap().hash);
+    }
 }
@@ -0,0 +1,543 @@
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{
+    board::Board,
+    movegen::{parse_san, Move},
+    pgn::{parse_pgn, GameResult, PgnGame},
+    piece::Color,
+    polyglot::{decode_move, encode_move, polyglot_key},
+};
+
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            raw_move: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+            learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookSelection {
+    Best,
+    Weighted,
+}
+
+// A Polyglot book: entries sorted by key, several per position.
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "book size is not a multiple of the entry size",
+            ));
+        }
+
+        let entries: Vec<_> = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(BookEntry::from_bytes)
+            .collect();
+
+        if !entries.is_sorted_by_key(|entry| entry.key) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "book entries are not sorted by key",
+            ));
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    // Legal book moves for the position with their weights. Entries whose
+    // move doesn't decode to a legal move are skipped.
+    pub fn moves(&self, board: &mut Board) -> Vec<(Move, u16)> {
+        self.entries(polyglot_key(board))
+            .iter()
+            .filter_map(|entry| Some((decode_move(board, entry.raw_move)?, entry.weight)))
+            .collect()
+    }
+
+    // `random` picks among weighted moves so callers control the randomness.
+    pub fn select(&self, board: &mut Board, selection: BookSelection, random: u64) -> Option<Move> {
+        let moves = self.moves(board);
+
+        match selection {
+            BookSelection::Best => moves
+                .iter()
+                .rev()
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(chess_move, _)| *chess_move),
+
+            BookSelection::Weighted => {
+                let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+                if total == 0 {
+                    return None;
+                }
+
+                let mut target = random % total;
+                for (chess_move, weight) in moves {
+                    if target < weight as u64 {
+                        return Some(chess_move);
+                    }
+                    target -= weight as u64;
+                }
+                None
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BuildOptions {
+    pub max_ply: usize,
+    pub min_games: u32,
+    // Minimum percentage scored by the side playing the move.
+    pub min_score: f64,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            max_ply: 30,
+            min_games: 3,
+            min_score: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct MoveStats {
+    games: u32,
+    // Two points per win and one per draw, as Polyglot weights count them.
+    points: u32,
+}
+
+pub struct BookBuilder {
+    options: BuildOptions,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+    skipped: usize,
+}
+
+impl BookBuilder {
+    pub fn new(options: BuildOptions) -> Self {
+        Self {
+            options,
+            stats: HashMap::new(),
+            games: 0,
+            skipped: 0,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    // Games without a decisive or drawn result, or whose start position
+    // can't be read, teach nothing and are skipped. Replay stops at the first
+    // move that doesn't parse.
+    pub fn add_game(&mut self, game: &PgnGame) {
+        let result = game.result();
+        let (Some(mut board), true) = (game.start_position(), result != GameResult::Unknown) else {
+            self.skipped += 1;
+            return;
+        };
+        self.games += 1;
+
+        for san in game.moves.iter().take(self.options.max_ply) {
+            let Some(chess_move) = parse_san(&mut board, san) else {
+                break;
+            };
+
+            let points = match (result, board.side_to_move) {
+                (GameResult::WhiteWins, Color::White) | (GameResult::BlackWins, Color::Black) => 2,
+                (GameResult::Draw, _) => 1,
+                _ => 0,
+            };
+
+            let stats = self
+                .stats
+                .entry((polyglot_key(&board), encode_move(&chess_move)))
+                .or_default();
+            stats.games += 1;
+            stats.points += points;
+
+            board.make_move(&chess_move);
+        }
+    }
+
+    pub fn add_pgn(&mut self, text: &str) {
+        for game in parse_pgn(text) {
+            self.add_game(&game);
+        }
+    }
+
+    // Entries passing the filters, sorted by key and then by weight, with
+    // weights scaled down where needed to fit Polyglot's 16 bits.
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let passing: Vec<_> = self
+            .stats
+            .iter()
+            .filter(|(_, stats)| {
+                stats.games >= self.options.min_games
+                    && stats.points as f64 * 50.0 / stats.games as f64 >= self.options.min_score
+            })
+            .collect();
+
+        let max_points = passing.iter().map(|(_, stats)| stats.points).max();
+        let scale = (max_points.unwrap_or(0) as f64 / u16::MAX as f64).max(1.0);
+
+        let mut entries: Vec<_> = passing
+            .into_iter()
+            .map(|(&(key, raw_move), stats)| BookEntry {
+                key,
+                raw_move,
+                weight: (stats.points as f64 / scale).round() as u16,
+                learn: 0,
+            })
+            .collect();
+
+        entries.sort_by_key(|entry| (entry.key, u16::MAX - entry.weight, entry.raw_move));
+        entries
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let entries = self.entries();
+        let bytes: Vec<_> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+
+        fs::write(path, bytes)?;
+        Ok(entries.len())
+    }
+}
+
+fn build_usage() {
+    println!(
+        "Usage: book build <output.bin> <games.pgn>... \
+         [--max-ply N] [--min-games N] [--min-score PERCENT]"
+    );
+}
+
+fn build(args: &[String]) {
+    let mut options = BuildOptions::default();
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let valid = match arg.as_str() {
+            "--max-ply" => args
+                .next()
+                .and_then(|value| value.parse().ok())
+                .map(|value| {
+                    options.max_ply = value;
+                }),
+            "--min-games" => args
+                .next()
+                .and_then(|value| value.parse().ok())
+                .map(|value| {
+                    options.min_games = value;
+                }),
+            "--min-score" => args
+                .next()
+                .and_then(|value| value.parse().ok())
+                .map(|value| {
+                    options.min_score = value;
+                }),
+            _ => {
+                paths.push(arg);
+                Some(())
+            }
+        };
+
+        if valid.is_none() {
+            println!("Invalid value for {}", arg);
+            return build_usage();
+        }
+    }
+
+    let Some((output, inputs)) = paths.split_first() else {
+        return build_usage();
+    };
+    if inputs.is_empty() {
+        return build_usage();
+    }
+
+    let mut builder = BookBuilder::new(options);
+    for input in inputs {
+        match fs::read_to_string(input) {
+            Ok(text) => builder.add_pgn(&text),
+            Err(error) => println!("Cannot read {}: {}", input, error),
+        }
+    }
+
+    match builder.write(output) {
+        Ok(entries) => println!(
+            "Wrote {} entries from {} games ({} skipped) to {}",
+            entries,
+            builder.games(),
+            builder.skipped(),
+            output
+        ),
+        Err(error) => println!("Cannot write {}: {}", output, error),
+    }
+}
+
+pub fn run(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("build") => build(&args[1..]),
+        _ => build_usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::STARTING_FEN_STRING, movegen::parse_uci_move};
+
+    fn book(moves: &[(&str, u16)]) -> Book {
+        let mut board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+        let key = polyglot_key(&board);
+
+        let mut bytes = Vec::new();
+        let filler = BookEntry {
+            key: key - 1,
+            raw_move: 0,
+            weight: 1,
+            learn: 0,
+        };
+        bytes.extend_from_slice(&filler.to_bytes());
+
+        for (uci, weight) in moves {
+            let chess_move = parse_uci_move(&mut board, uci).unwrap();
+            let entry = BookEntry {
+                key,
+                raw_move: encode_move(&chess_move),
+                weight: *weight,
+                learn: 0,
+            };
+            bytes.extend_from_slice(&entry.to_bytes());
+        }
+
+        let filler = BookEntry {
+            key: key + 1,
+            ..filler
+        };
+        bytes.extend_from_slice(&filler.to_bytes());
+
+        Book::from_bytes(&bytes).unwrap()
+    }
+
+    fn select(book: &Book, selection: BookSelection, random: u64) -> Option<String> {
+        let mut board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+        book.select(&mut board, selection, random)
+            .map(|chess_move| chess_move.to_uci())
+    }
+
+    #[test]
+    fn probes_only_matching_entries() {
+        let book = book(&[("e2e4", 10), ("d2d4", 30), ("c2c4", 5)]);
+        let mut board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+
+        assert_eq!(book.len(), 5);
+        assert_eq!(book.moves(&mut board).len(), 3);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(book.moves(&mut board).is_empty());
+    }
+
+    #[test]
+    fn best_and_weighted_selection() {
+        let book = book(&[("e2e4", 10), ("d2d4", 30), ("c2c4", 5)]);
+
+        assert_eq!(select(&book, BookSelection::Best, 0), Some("d2d4".into()));
+        assert_eq!(
+            select(&book, BookSelection::Weighted, 0),
+            Some("e2e4".into())
+        );
+        assert_eq!(
+            select(&book, BookSelection::Weighted, 10),
+            Some("d2d4".into())
+        );
+        assert_eq!(
+            select(&book, BookSelection::Weighted, 44),
+            Some("c2c4".into())
+        );
+        assert_eq!(
+            select(&book, BookSelection::Weighted, 45),
+            Some("e2e4".into())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_books() {
+        assert!(Book::from_bytes(&[0; 15]).is_err());
+
+        let mut bytes = Vec::new();
+        for key in [2, 1] {
+            let entry = BookEntry {
+                key,
+                raw_move: 0,
+                weight: 1,
+                learn: 0,
+            };
+            bytes.extend_from_slice(&entry.to_bytes());
+        }
+        assert!(Book::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn builds_book_from_games() {
+        let pgn = "\
+[Result \"1-0\"]
+1. e4 e5 2. Nf3 1-0
+
+[Result \"1/2-1/2\"]
+1. e4 c5 2. Nf3 1/2-1/2
+
+[Result \"0-1\"]
+1. d4 d5 0-1
+
+[Result \"*\"]
+1. c4 *
+";
+        let options = BuildOptions {
+            max_ply: 2,
+            min_games: 1,
+            min_score: 0.0,
+        };
+        let mut builder = BookBuilder::new(options);
+        builder.add_pgn(pgn);
+
+        assert_eq!(builder.games(), 3);
+        assert_eq!(builder.skipped(), 1);
+
+        let entries = builder.entries();
+        // e4, d4, and the replies e5, c5, d5; Nf3 lies past the ply limit.
+        assert_eq!(entries.len(), 5);
+        assert!(entries.is_sorted_by_key(|entry| entry.key));
+
+        let bytes: Vec<_> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+        let book = Book::from_bytes(&bytes).unwrap();
+        let mut board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+
+        let moves: Vec<_> = book
+            .moves(&mut board)
+            .into_iter()
+            .map(|(chess_move, weight)| (chess_move.to_uci(), weight))
+            .collect();
+        assert_eq!(moves, [("e2e4".to_string(), 3), ("d2d4".to_string(), 0)]);
+    }
+
+    #[test]
+    fn replays_games_past_zero_castling() {
+        let pgn = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 Nf6 1-0";
+        let options = BuildOptions {
+            max_ply: 8,
+            min_games: 1,
+            min_score: 0.0,
+        };
+        let mut builder = BookBuilder::new(options);
+        builder.add_pgn(pgn);
+
+        let bytes: Vec<_> = builder
+            .entries()
+            .iter()
+            .flat_map(|entry| entry.to_bytes())
+            .collect();
+        let book = Book::from_bytes(&bytes).unwrap();
+        let mut board =
+            Board::from_fen("r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4")
+                .unwrap();
+
+        let moves: Vec<_> = book
+            .moves(&mut board)
+            .into_iter()
+            .map(|(chess_move, _)| chess_move.to_uci())
+            .collect();
+        assert_eq!(moves, ["g8f6"]);
+    }
+
+    #[test]
+    fn filters_by_games_and_score() {
+        let pgn = "\
+[Result \"1-0\"]
+1. e4 e5 1-0
+
+[Result \"0-1\"]
+1. e4 c5 0-1
+
+[Result \"1-0\"]
+1. d4 d5 1-0
+";
+        let mut builder = BookBuilder::new(BuildOptions {
+            max_ply: 1,
+            min_games: 2,
+            min_score: 0.0,
+        });
+        builder.add_pgn(pgn);
+        let entries = builder.entries();
+        assert_eq!(entries.len(), 1);
+
+        let mut board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+        let e2e4 = parse_uci_move(&mut board, "e2e4").unwrap();
+        assert_eq!(entries[0].raw_move, encode_move(&e2e4));
+
+        let mut builder = BookBuilder::new(BuildOptions {
+            max_ply: 1,
+            min_games: 1,
+            min_score: 60.0,
+        });
+        builder.add_pgn(pgn);
+        let entries = builder.entries();
+        assert_eq!(entries.len(), 1);
+
+        let d2d4 = parse_uci_move(&mut board, "d2d4").unwrap();
+        assert_eq!(entries[0].raw_move, encode_move(&d2d4));
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use crate::{
+    board::{Board, STARTING_FEN_STRING},
+    movegen::{game_status, in_check, legal_moves, GameStatus},
+    pgn::GameResult,
+    piece::{Color, Piece},
+    search::{SearchLimits, SearchOptions, Searcher, MATE, MAX_PLY},
+    tt::TranspositionTable,
+    zobrist::split_mix,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    // "<fen> | <score> | <result>" per line, with the result as 1.0, 0.5 or
+    // 0.0 for White.
+    Text,
+    // 32 bytes per position, see `pack`.
+    Binary,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DatagenOptions {
+    pub games: usize,
+    pub threads: usize,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub random_plies: usize,
+    pub max_plies: usize,
+    pub seed: u64,
+    pub format: DataFormat,
+}
+
+impl Default for DatagenOptions {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            threads: 1,
+            depth: None,
+            nodes: None,
+            random_plies: 8,
+            max_plies: 400,
+            seed: 0,
+            format: DataFormat::Text,
+        }
+    }
+}
+
+// A position with its search score and the game's result, both from White's
+// point of view.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub fen: String,
+    pub score: i32,
+    pub result: GameResult,
+}
+
+fn result_value(result: GameResult) -> Option<f64> {
+    match result {
+        GameResult::WhiteWins => Some(1.0),
+        GameResult::Draw => Some(0.5),
+        GameResult::BlackWins => Some(0.0),
+        GameResult::Unknown => None,
+    }
+}
+
+impl Sample {
+    pub fn to_text(&self) -> String {
+        let result = result_value(self.result).unwrap_or(0.5);
+        format!("{} | {} | {:.1}", self.fen, self.score, result)
+    }
+
+    pub fn from_text(line: &str) -> Option<Self> {
+        let mut fields = line.split('|').map(str::trim);
+        let fen = fields.next()?;
+        let score = fields.next()?.parse().ok()?;
+        let result = match fields.next()? {
+            "1.0" | "1" | "1-0" => GameResult::WhiteWins,
+            "0.5" | "1/2-1/2" => GameResult::Draw,
+            "0.0" | "0" | "0-1" => GameResult::BlackWins,
+            _ => return None,
+        };
+
+        Board::from_fen(fen)?;
+        Some(Self {
+            fen: fen.to_string(),
+            score,
+            result,
+        })
+    }
+
+    // Occupancy in board square order (a8 first), then a nibble per
+    // occupied square holding the piece index, then the score, result (0, 1
+    // or 2 for a black win, draw or white win), side to move, castling flags,
+    // en passant square (64 for none) and halfmove clock.
+    pub fn pack(&self) -> Option<[u8; 32]> {
+        let board = Board::from_fen(&self.fen)?;
+        let mut bytes = [0; 32];
+
+        let squares = board.occupied[0].set_bit_indices();
+        let occupancy = squares.iter().fold(0u64, |bits, square| bits | 1 << square);
+        bytes[..8].copy_from_slice(&occupancy.to_le_bytes());
+
+        for (index, square) in squares.iter().enumerate() {
+            let piece = board.get(square / 8, square % 8)?.index() as u8;
+            bytes[8 + index / 2] |= piece << (4 * (index % 2));
+        }
+
+        let score = self.score.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        bytes[24..26].copy_from_slice(&score.to_le_bytes());
+        bytes[26] = (2.0 * result_value(self.result)?) as u8;
+        bytes[27] = (board.side_to_move == Color::Black) as u8;
+        bytes[28] = board
+            .can_castle
+            .iter()
+            .enumerate()
+            .fold(0, |flags, (index, &flag)| flags | (flag as u8) << index);
+        bytes[29] = board.en_passant.map_or(64, |(rank, file)| rank * 8 + file);
+        bytes[30] = board.halfmove_clock.min(u8::MAX as u32) as u8;
+
+        Some(bytes)
+    }
+
+    pub fn unpack(bytes: &[u8; 32]) -> Option<Self> {
+        let occupancy = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let mut board = Board::new();
+
+        let squares = (0..64).filter(|square| occupancy & 1 << square != 0);
+        for (index, square) in squares.enumerate() {
+            let piece = bytes[8 + index / 2] >> (4 * (index % 2)) & 0xf;
+            board.set(square / 8, square % 8, Piece::from_index(piece as usize)?);
+        }
+
+        board.side_to_move = if bytes[27] == 0 {
+            Color::White
+        } else {
+            Color::Black
+        };
+        for (index, flag) in board.can_castle.iter_mut().enumerate() {
+            *flag = bytes[28] & 1 << index != 0;
+        }
+        board.en_passant = (bytes[29] < 64).then_some((bytes[29] / 8, bytes[29] % 8));
+        board.halfmove_clock = bytes[30] as u32;
+
+        let result = match bytes[26] {
+            0 => GameResult::BlackWins,
+            1 => GameResult::Draw,
+            2 => GameResult::WhiteWins,
+            _ => return None,
+        };
+
+        Some(Self {
+            fen: board.to_fen(),
+            score: i16::from_le_bytes([bytes[24], bytes[25]]) as i32,
+            result,
+        })
+    }
+}
+
+// Deterministic random numbers for one game, so that every game depends only
+// on the seed and its number.
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64, game: usize) -> Self {
+        Self(split_mix(seed ^ (game as u64).wrapping_mul(0x9e3779b97f4a7c15)).1)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        let (state, value) = split_mix(self.0);
+        self.0 = state;
+        (value % bound as u64) as usize
+    }
+}
+
+// Plays one game from a random opening, keeping the quiet positions that
+// weren't in check. Games that end during the opening produce nothing.
+fn play_game(
+    game: usize,
+    options: &DatagenOptions,
+    tt: &Arc<TranspositionTable>,
+    stop: &Arc<AtomicBool>,
+) -> Vec<Sample> {
+    let mut random = Random::new(options.seed, game);
+    let mut board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+
+    for _ in 0..options.random_plies {
+        let moves = legal_moves(&mut board);
+        if moves.is_empty() {
+            return Vec::new();
+        }
+        board.make_move(&moves[random.below(moves.len())]);
+    }
+    if legal_moves(&mut board).is_empty() {
+        return Vec::new();
+    }
+
+    let limits = SearchLimits {
+        depth: options.depth,
+        nodes: options.nodes,
+        ..SearchLimits::default()
+    };
+    let search_options = SearchOptions {
+        threads: 1,
+        multipv: 1,
+        ..SearchOptions::default()
+    };
+    tt.clear();
+
+    let mut positions = Vec::new();
+    let result = loop {
+        match game_status(&mut board) {
+            GameStatus::Checkmate(Color::White) => break GameResult::WhiteWins,
+            GameStatus::Checkmate(Color::Black) => break GameResult::BlackWins,
+            GameStatus::VariantWin(Color::White) => break GameResult::WhiteWins,
+            GameStatus::VariantWin(Color::Black) => break GameResult::BlackWins,
+            GameStatus::Ongoing if positions.len() < options.max_plies => {}
+            _ => break GameResult::Draw,
+        }
+
+        let mut score = None;
+        let mut searcher = Searcher::with_tt(
+            board.clone(),
+            limits,
+            search_options,
+            stop.clone(),
+            tt.clone(),
+        );
+        let Some(best_move) = searcher.search(|info| score = Some(info.score)) else {
+            break GameResult::Draw;
+        };
+
+        if let Some(score) = score {
+            let white_score = match board.side_to_move {
+                Color::White => score,
+                Color::Black => -score,
+            };
+
+            // A found mate decides the game.
+            if score.abs() >= MATE - MAX_PLY as i32 {
+                break if white_score > 0 {
+                    GameResult::WhiteWins
+                } else {
+                    GameResult::BlackWins
+                };
+            }
+
+            if !in_check(&board, board.side_to_move) && !best_move.is_noisy() {
+                positions.push(Some((board.to_fen(), white_score)));
+            } else {
+                positions.push(None);
+            }
+        } else {
+            positions.push(None);
+        }
+
+        board.make_move(&best_move);
+    };
+
+    positions
+        .into_iter()
+        .flatten()
+        .map(|(fen, score)| Sample { fen, score, result })
+        .collect()
+}
+
+// Plays the games on several threads and hands them over in game order, so
+// the output depends only on the options and not on thread timing.
+pub fn generate(options: &DatagenOptions, mut on_game: impl FnMut(Vec<Sample>)) {
+    let next_game = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..options.threads.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+
+            scope.spawn(move || {
+                let tt = Arc::new(TranspositionTable::new(SearchOptions::default().hash));
+                let stop = Arc::new(AtomicBool::new(false));
+
+                loop {
+                    let game = next_game.fetch_add(1, Ordering::Relaxed);
+                    if game >= options.games {
+                        break;
+                    }
+
+                    let samples = play_game(game, options, &tt, &stop);
+                    if sender.send((game, samples)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (game, samples) in receiver {
+            pending.insert(game, samples);
+            while let Some(samples) = pending.remove(&next) {
+                on_game(samples);
+                next += 1;
+            }
+        }
+    });
+}
+
+fn write_samples(
+    writer: &mut impl Write,
+    samples: &[Sample],
+    format: DataFormat,
+) -> io::Result<()> {
+    for sample in samples {
+        match format {
+            DataFormat::Text => writeln!(writer, "{}", sample.to_text())?,
+            DataFormat::Binary => {
+                if let Some(bytes) = sample.pack() {
+                    writer.write_all(&bytes)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn datagen(output: impl AsRef<Path>, options: &DatagenOptions) -> io::Result<usize> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    let mut written = 0;
+    let mut error = None;
+
+    generate(options, |samples| {
+        if error.is_none() {
+            match write_samples(&mut writer, &samples, options.format) {
+                Ok(()) => written += samples.len(),
+                Err(write_error) => error = Some(write_error),
+            }
+        }
+    });
+
+    match error {
+        Some(error) => Err(error),
+        None => writer.flush().map(|()| written),
+    }
+}
+
+fn usage() {
+    println!(
+        "Usage: datagen <output> [--games N] [--threads N] [--depth N | --nodes N] \
+         [--random-plies N] [--max-plies N] [--seed N] [--format text|binary]"
+    );
+}
+
+pub fn run(args: &[String]) {
+    let mut options = DatagenOptions::default();
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(String::as_str);
+        let valid = match arg.as_str() {
+            "--games" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.games = value;
+            }),
+            "--threads" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.threads = value;
+            }),
+            "--depth" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.depth = Some(value);
+            }),
+            "--nodes" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.nodes = Some(value);
+            }),
+            "--random-plies" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.random_plies = value;
+            }),
+            "--max-plies" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.max_plies = value;
+            }),
+            "--seed" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.seed = value;
+            }),
+            "--format" => {
+                let format = match value() {
+                    Some("text") => Some(DataFormat::Text),
+                    Some("binary") => Some(DataFormat::Binary),
+                    _ => None,
+                };
+                format.map(|format| {
+                    options.format = format;
+                })
+            }
+            _ if output.is_none() && !arg.starts_with("--") => {
+                output = Some(arg);
+                Some(())
+            }
+            _ => None,
+        };
+
+        if valid.is_none() {
+            println!("Invalid argument {}", arg);
+            return usage();
+        }
+    }
+
+    let Some(output) = output else {
+        return usage();
+    };
+    if options.depth.is_none() && options.nodes.is_none() {
+        options.depth = Some(8);
+    }
+
+    match datagen(output, &options) {
+        Ok(positions) => println!(
+            "Wrote {} positions from {} games to {}",
+            positions, options.games, output
+        ),
+        Err(error) => println!("Cannot write {}: {}", output, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(options: &DatagenOptions) -> Vec<Sample> {
+        let mut samples = Vec::new();
+        generate(options, |game| samples.extend(game));
+        samples
+    }
+
+    #[test]
+    fn text_and_binary_round_trip() {
+        let sample = Sample {
+            fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b Kq e3 7 1".into(),
+            score: -153,
+            result: GameResult::BlackWins,
+        };
+
+        assert_eq!(Sample::from_text(&sample.to_text()), Some(sample.clone()));
+        assert_eq!(sample.to_text().split(" | ").last(), Some("0.0"));
+        assert_eq!(Sample::unpack(&sample.pack().unwrap()), Some(sample));
+    }
+
+    #[test]
+    fn games_are_reproducible_from_the_seed() {
+        let options = DatagenOptions {
+            games: 3,
+            depth: Some(2),
+            random_plies: 6,
+            max_plies: 40,
+            seed: 7,
+            ..DatagenOptions::default()
+        };
+
+        let single = samples(&options);
+        assert!(!single.is_empty());
+        assert_eq!(
+            samples(&DatagenOptions {
+                threads: 2,
+                ..options
+            }),
+            single
+        );
+        assert_ne!(samples(&DatagenOptions { seed: 8, ..options }), single);
+
+        for sample in &single {
+            let mut board = Board::from_fen(&sample.fen).unwrap();
+            assert!(!in_check(&board, board.side_to_move));
+            assert_eq!(game_status(&mut board), GameStatus::Ongoing);
+        }
+    }
+}
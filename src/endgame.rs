@@ -0,0 +1,452 @@
+use std::sync::OnceLock;
+
+use crate::{
+    board::Board,
+    eval::PIECE_VALUES,
+    piece::{Color, Piece},
+};
+
+// Scores for endings that are known wins, far above any material balance but
+// below mate scores.
+pub const KNOWN_WIN: i32 = 10000;
+
+// Squares in this module count from a1 = 0 to h8 = 63 with the strong side
+// playing up the board, so kings and pawns can be mirrored freely.
+fn relative_square(board_square: (u8, u8), strong: Color) -> usize {
+    let (rank, file) = board_square;
+    let row = match strong {
+        Color::White => 7 - rank,
+        Color::Black => rank,
+    };
+    row as usize * 8 + file as usize
+}
+
+fn row(square: usize) -> usize {
+    square / 8
+}
+
+fn file(square: usize) -> usize {
+    square % 8
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    row(a).abs_diff(row(b)).max(file(a).abs_diff(file(b)))
+}
+
+fn neighbours(square: usize) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |&other| distance(square, other) == 1)
+}
+
+fn pawn_attacks(pawn: usize, square: usize) -> bool {
+    row(square) == row(pawn) + 1 && file(square).abs_diff(file(pawn)) == 1
+}
+
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 4;
+
+// Pawns on files a-d and rows 2-7; the other files mirror onto these.
+const KPK_SIZE: usize = 2 * 24 * 64 * 64;
+
+fn kpk_index(strong_to_move: bool, strong_king: usize, weak_king: usize, pawn: usize) -> usize {
+    let pawn_index = (row(pawn) - 1) * 4 + file(pawn);
+    ((strong_to_move as usize * 24 + pawn_index) * 64 + strong_king) * 64 + weak_king
+}
+
+fn kpk_initial(strong_to_move: bool, strong_king: usize, weak_king: usize, pawn: usize) -> u8 {
+    let promotion = pawn + 8;
+
+    if distance(strong_king, weak_king) <= 1
+        || strong_king == pawn
+        || weak_king == pawn
+        || (strong_to_move && pawn_attacks(pawn, weak_king))
+    {
+        return INVALID;
+    }
+
+    if strong_to_move
+        && row(pawn) == 6
+        && strong_king != promotion
+        && (distance(weak_king, promotion) > 1 || distance(strong_king, promotion) == 1)
+    {
+        return WIN;
+    }
+
+    if !strong_to_move {
+        let stalemated = neighbours(weak_king)
+            .all(|square| distance(square, strong_king) <= 1 || pawn_attacks(pawn, square));
+        let captures = distance(weak_king, pawn) == 1 && distance(strong_king, pawn) > 1;
+
+        if stalemated || captures {
+            return DRAW;
+        }
+    }
+
+    UNKNOWN
+}
+
+fn kpk_classify(
+    results: &[u8],
+    strong_to_move: bool,
+    strong_king: usize,
+    weak_king: usize,
+    pawn: usize,
+) -> u8 {
+    // Moves into illegal positions land on INVALID entries and add nothing.
+    let mut reachable = 0;
+
+    if strong_to_move {
+        for square in neighbours(strong_king) {
+            reachable |= results[kpk_index(false, square, weak_king, pawn)];
+        }
+
+        if row(pawn) < 6 {
+            reachable |= results[kpk_index(false, strong_king, weak_king, pawn + 8)];
+        }
+        if row(pawn) == 1 && pawn + 8 != strong_king && pawn + 8 != weak_king {
+            reachable |= results[kpk_index(false, strong_king, weak_king, pawn + 16)];
+        }
+
+        if reachable & WIN != 0 {
+            WIN
+        } else if reachable & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            DRAW
+        }
+    } else {
+        for square in neighbours(weak_king) {
+            reachable |= results[kpk_index(true, strong_king, square, pawn)];
+        }
+
+        if reachable & DRAW != 0 {
+            DRAW
+        } else if reachable & UNKNOWN != 0 {
+            UNKNOWN
+        } else {
+            WIN
+        }
+    }
+}
+
+fn generate_kpk() -> Vec<u64> {
+    let mut positions = Vec::with_capacity(KPK_SIZE);
+    let mut results = vec![INVALID; KPK_SIZE];
+
+    for strong_to_move in [false, true] {
+        for pawn_row in 1..7 {
+            for pawn_file in 0..4 {
+                for strong_king in 0..64 {
+                    for weak_king in 0..64 {
+                        let pawn = pawn_row * 8 + pawn_file;
+                        let index = kpk_index(strong_to_move, strong_king, weak_king, pawn);
+                        results[index] = kpk_initial(strong_to_move, strong_king, weak_king, pawn);
+                        positions.push((strong_to_move, strong_king, weak_king, pawn));
+                    }
+                }
+            }
+        }
+    }
+
+    // Resolve unknown positions from their successors until nothing changes;
+    // whatever is still unknown then can't be forced to a win.
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for &(strong_to_move, strong_king, weak_king, pawn) in &positions {
+            let index = kpk_index(strong_to_move, strong_king, weak_king, pawn);
+            if results[index] != UNKNOWN {
+                continue;
+            }
+
+            let result = kpk_classify(&results, strong_to_move, strong_king, weak_king, pawn);
+            if result != UNKNOWN {
+                results[index] = result;
+                changed = true;
+            }
+        }
+    }
+
+    let mut bits = vec![0; KPK_SIZE / 64];
+    for (index, result) in results.into_iter().enumerate() {
+        if result == WIN {
+            bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+    bits
+}
+
+// Whether the side with the pawn wins. Generated on first use.
+fn kpk_probe(strong_to_move: bool, strong_king: usize, weak_king: usize, pawn: usize) -> bool {
+    static KPK: OnceLock<Vec<u64>> = OnceLock::new();
+    let bits = KPK.get_or_init(generate_kpk);
+
+    let mirror = |square: usize| {
+        if file(pawn) > 3 {
+            square ^ 7
+        } else {
+            square
+        }
+    };
+
+    let index = kpk_index(
+        strong_to_move,
+        mirror(strong_king),
+        mirror(weak_king),
+        mirror(pawn),
+    );
+    bits[index / 64] & (1 << (index % 64)) != 0
+}
+
+// Piece counts indexed like `Board::pieces`.
+pub fn material(board: &Board) -> [u32; 12] {
+    board.pieces.map(|bitset| bitset.count())
+}
+
+fn count(material: &[u32; 12], kind: usize, color: Color) -> u32 {
+    material[Piece::from_kind(kind, color).unwrap().index()]
+}
+
+// Everything except the king, as counts of pawn, knight, bishop, rook and
+// queen.
+fn side(material: &[u32; 12], color: Color) -> [u32; 5] {
+    [0, 1, 2, 3, 4].map(|kind| count(material, kind, color))
+}
+
+fn squares(board: &Board, kind: usize, color: Color) -> Vec<(u8, u8)> {
+    let piece = Piece::from_kind(kind, color).unwrap();
+    board.pieces[piece.index()]
+        .set_bit_indices()
+        .into_iter()
+        .map(|square| (square / 8, square % 8))
+        .collect()
+}
+
+fn is_light((rank, file): (u8, u8)) -> bool {
+    (rank + file) % 2 == 0
+}
+
+// How an ending overrides the normal evaluation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    // A score from White's point of view.
+    Exact(i32),
+    // Scale the normal evaluation by this many 64ths.
+    Scale(i32),
+}
+
+fn push_to_edge(square: usize) -> i32 {
+    let row_edge = row(square).min(7 - row(square));
+    let file_edge = file(square).min(7 - file(square));
+    20 * (6 - row_edge - file_edge) as i32
+}
+
+fn push_close(a: usize, b: usize) -> i32 {
+    10 * (7 - distance(a, b)) as i32
+}
+
+fn kpk(board: &Board, strong: Color) -> Verdict {
+    let strong_king = relative_square(board.king_square(strong).unwrap(), strong);
+    let weak_king = relative_square(board.king_square(strong.opposite()).unwrap(), strong);
+    let pawn = relative_square(squares(board, 0, strong)[0], strong);
+
+    if kpk_probe(board.side_to_move == strong, strong_king, weak_king, pawn) {
+        Verdict::Exact(KNOWN_WIN + PIECE_VALUES[0] + 10 * row(pawn) as i32)
+    } else {
+        Verdict::Exact(0)
+    }
+}
+
+// KQK and KRK: drive the lone king to the edge with the king's help.
+fn mate_with_major(board: &Board, strong: Color, kind: usize) -> Verdict {
+    let strong_king = relative_square(board.king_square(strong).unwrap(), strong);
+    let weak_king = relative_square(board.king_square(strong.opposite()).unwrap(), strong);
+
+    Verdict::Exact(
+        KNOWN_WIN
+            + PIECE_VALUES[kind]
+            + push_to_edge(weak_king)
+            + push_close(strong_king, weak_king),
+    )
+}
+
+// KBNK: mate is only possible in a corner of the bishop's colour.
+fn kbnk(board: &Board, strong: Color) -> Verdict {
+    let strong_king = relative_square(board.king_square(strong).unwrap(), strong);
+    let weak_king_square = board.king_square(strong.opposite()).unwrap();
+    let weak_king = weak_king_square.0 as usize * 8 + weak_king_square.1 as usize;
+
+    let corners: [usize; 2] = if is_light(squares(board, 2, strong)[0]) {
+        [0, 63]
+    } else {
+        [7, 56]
+    };
+    let corner_distance = corners
+        .iter()
+        .map(|&corner| distance(weak_king, corner))
+        .min()
+        .unwrap();
+    let weak_king = relative_square(weak_king_square, strong);
+
+    Verdict::Exact(
+        KNOWN_WIN
+            + PIECE_VALUES[1]
+            + PIECE_VALUES[2]
+            + 40 * (7 - corner_distance) as i32
+            + push_close(strong_king, weak_king),
+    )
+}
+
+// A bishop with rook pawns on one file can't win once the defending king
+// reaches the promotion corner, if the bishop doesn't control that square.
+fn wrong_rook_pawn(board: &Board, strong: Color) -> Option<Verdict> {
+    let pawns = squares(board, 0, strong);
+    let pawn_file = pawns[0].1;
+    if !(pawn_file == 0 || pawn_file == 7) || pawns.iter().any(|pawn| pawn.1 != pawn_file) {
+        return None;
+    }
+
+    let promotion = match strong {
+        Color::White => (0, pawn_file),
+        Color::Black => (7, pawn_file),
+    };
+    if is_light(squares(board, 2, strong)[0]) == is_light(promotion) {
+        return None;
+    }
+
+    let weak_king = board.king_square(strong.opposite()).unwrap();
+    let king_distance = weak_king
+        .0
+        .abs_diff(promotion.0)
+        .max(weak_king.1.abs_diff(promotion.1));
+    (king_distance <= 1).then_some(Verdict::Exact(0))
+}
+
+fn opposite_bishops(board: &Board) -> Verdict {
+    let white = squares(board, 2, Color::White)[0];
+    let black = squares(board, 2, Color::Black)[0];
+
+    if is_light(white) != is_light(black) {
+        Verdict::Scale(32)
+    } else {
+        Verdict::Scale(64)
+    }
+}
+
+// Specialised knowledge for the ending on the board, selected by material,
+// as a score from White's point of view where exact.
+pub fn probe(board: &Board) -> Option<Verdict> {
+    let material = material(board);
+
+    for strong in [Color::White, Color::Black] {
+        let weak = side(&material, strong.opposite());
+        if weak != [0; 5] {
+            continue;
+        }
+
+        let verdict = match side(&material, strong) {
+            [1, 0, 0, 0, 0] => Some(kpk(board, strong)),
+            [0, 0, 0, 1, 0] => Some(mate_with_major(board, strong, 3)),
+            [0, 0, 0, 0, 1] => Some(mate_with_major(board, strong, 4)),
+            [0, 1, 1, 0, 0] => Some(kbnk(board, strong)),
+            [pawns, 0, 1, 0, 0] if pawns > 0 => wrong_rook_pawn(board, strong),
+            _ => None,
+        };
+
+        return verdict.map(|verdict| match (verdict, strong) {
+            (Verdict::Exact(score), Color::Black) => Verdict::Exact(-score),
+            _ => verdict,
+        });
+    }
+
+    let white = side(&material, Color::White);
+    let black = side(&material, Color::Black);
+    if white[1..] == [0, 1, 0, 0] && black[1..] == [0, 1, 0, 0] {
+        return Some(opposite_bishops(board));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kpk_wins(fen: &str) -> bool {
+        let board = Board::from_fen(fen).unwrap();
+        matches!(probe(&board), Some(Verdict::Exact(score)) if score.abs() >= KNOWN_WIN)
+    }
+
+    #[test]
+    fn kpk_bitbase() {
+        // King in front of the pawn on the sixth wins whoever moves.
+        assert!(kpk_wins("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"));
+        assert!(kpk_wins("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"));
+
+        // Stalemate.
+        assert!(!kpk_wins("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"));
+
+        // Rook pawn with the defender in the corner.
+        assert!(!kpk_wins("k7/8/1K6/P7/8/8/8/8 w - - 0 1"));
+
+        // Outside the square of the pawn.
+        assert!(kpk_wins("7k/8/8/8/P7/8/8/7K b - - 0 1"));
+
+        // The pawn falls.
+        assert!(!kpk_wins("8/8/8/4k3/4P3/8/8/7K b - - 0 1"));
+
+        // Black's pawn, mirrored onto the other wing.
+        assert!(
+            kpk_wins("8/8/8/8/8/3k4/3p4/3K4 b - - 0 1")
+                == kpk_wins("3k4/3P4/3K4/8/8/8/8/8 w - - 0 1")
+        );
+    }
+
+    #[test]
+    fn lone_king_is_driven_to_the_edge() {
+        let centre = Board::from_fen("8/8/8/3k4/8/8/8/R3K3 w - - 0 1").unwrap();
+        let edge = Board::from_fen("3k4/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+
+        let (Some(Verdict::Exact(centre)), Some(Verdict::Exact(edge))) =
+            (probe(&centre), probe(&edge))
+        else {
+            panic!("KRK not recognised");
+        };
+        assert!(centre > KNOWN_WIN);
+        assert!(edge > centre);
+
+        let black = Board::from_fen("8/8/8/3K4/8/8/8/q3k3 w - - 0 1").unwrap();
+        assert!(matches!(probe(&black), Some(Verdict::Exact(score)) if score < -KNOWN_WIN));
+    }
+
+    #[test]
+    fn kbnk_prefers_the_bishops_corner() {
+        // Light-squared bishop on f1: a8 and h1 are the mating corners.
+        let right = Board::from_fen("k7/8/8/8/8/8/8/4KBN1 w - - 0 1").unwrap();
+        let wrong = Board::from_fen("7k/8/8/8/8/8/8/4KBN1 w - - 0 1").unwrap();
+
+        let (Some(Verdict::Exact(right)), Some(Verdict::Exact(wrong))) =
+            (probe(&right), probe(&wrong))
+        else {
+            panic!("KBNK not recognised");
+        };
+        assert!(right > wrong);
+    }
+
+    #[test]
+    fn drawish_endings() {
+        let wrong_corner = Board::from_fen("7k/8/8/7P/8/8/8/3BK3 w - - 0 1").unwrap();
+        assert_eq!(probe(&wrong_corner), Some(Verdict::Exact(0)));
+
+        let right_corner = Board::from_fen("7k/8/8/7P/8/8/8/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(probe(&right_corner), None);
+
+        let opposite = Board::from_fen("4k3/5p2/4b3/8/8/2B5/4PP2/4K3 w - - 0 1").unwrap();
+        assert_eq!(probe(&opposite), Some(Verdict::Scale(32)));
+
+        let same = Board::from_fen("4k3/5p2/3b4/8/8/2B5/4PP2/4K3 w - - 0 1").unwrap();
+        assert_eq!(probe(&same), Some(Verdict::Scale(64)));
+    }
+}
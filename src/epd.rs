@@ -0,0 +1,479 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use crate::{
+    board::Board,
+    movegen::{parse_san, to_san, Move},
+    search::{SearchLimits, SearchOptions, Searcher, MATE, MAX_PLY},
+    tt::TranspositionTable,
+};
+
+// A position in Extended Position Description: the first four FEN fields
+// followed by operations such as `bm Qg6; id "WAC.001";`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epd {
+    pub position: String,
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+// Splits the operations into opcodes and operands. Quoted operands may hold
+// spaces and semicolons.
+fn parse_operations(text: &str) -> Option<Vec<(String, Vec<String>)>> {
+    let mut operations = Vec::new();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => quoted.push(chars.next()?),
+                        c => quoted.push(c),
+                    }
+                }
+                words.push(quoted);
+            }
+            ';' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    operations.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+
+    // A last operation may be missing its semicolon.
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+    Some(operations)
+}
+
+impl Epd {
+    // Also reads full FENs, keeping the move counters as `hmvc` and `fmvn`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let mut rest = line;
+        let mut fields = Vec::new();
+        for _ in 0..4 {
+            let (field, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            fields.push(field);
+            rest = remainder.trim_start();
+        }
+
+        let position = fields.join(" ");
+        Board::from_fen(&position)?;
+
+        let mut operations = Vec::new();
+        let counters: Vec<_> = rest.split_whitespace().take(2).collect();
+        if let [halfmove, fullmove] = counters[..] {
+            if halfmove.parse::<u32>().is_ok() && fullmove.parse::<u32>().is_ok() {
+                operations.push(("hmvc".to_string(), vec![halfmove.to_string()]));
+                operations.push(("fmvn".to_string(), vec![fullmove.to_string()]));
+                rest = rest
+                    .trim_start_matches(|c: char| c.is_ascii_digit() || c.is_whitespace())
+                    .trim_start();
+            }
+        }
+        operations.extend(parse_operations(rest)?);
+
+        Some(Self {
+            position,
+            operations,
+        })
+    }
+
+    pub fn from_board(board: &Board) -> Self {
+        let fen = board.to_fen();
+        let fields: Vec<_> = fen.split_whitespace().collect();
+
+        let mut epd = Self {
+            position: fields[..4].join(" "),
+            operations: Vec::new(),
+        };
+        if board.halfmove_clock != 0 || board.fullmove_number != 1 {
+            epd.set_operation("hmvc", vec![fields[4].to_string()]);
+            epd.set_operation("fmvn", vec![fields[5].to_string()]);
+        }
+        epd
+    }
+
+    pub fn operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(name, _)| name == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    // Replaces the operation if present, or adds it at the end.
+    pub fn set_operation(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(name, _)| name == opcode) {
+            Some((_, existing)) => *existing = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn remove_operation(&mut self, opcode: &str) {
+        self.operations.retain(|(name, _)| name != opcode);
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operation("id")?.first().map(String::as_str)
+    }
+
+    pub fn board(&self) -> Option<Board> {
+        let counter = |opcode: &str, default: &str| {
+            self.operation(opcode)
+                .and_then(|operands| operands.first())
+                .map_or(default.to_string(), String::clone)
+        };
+
+        Board::from_fen(&format!(
+            "{} {} {}",
+            self.position,
+            counter("hmvc", "0"),
+            counter("fmvn", "1")
+        ))
+    }
+
+    // The moves of a move-valued operation such as `bm` or `am`, written in
+    // SAN. None if any of them isn't legal here.
+    pub fn moves(&self, opcode: &str) -> Option<Vec<Move>> {
+        let mut board = self.board()?;
+        self.operation(opcode)?
+            .iter()
+            .map(|san| parse_san(&mut board, san))
+            .collect()
+    }
+}
+
+impl Display for Epd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.position)?;
+
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+
+            // Comments and ids are conventionally quoted.
+            let always_quoted = opcode == "id"
+                || (opcode.len() == 2
+                    && opcode.starts_with('c')
+                    && opcode.ends_with(|c: char| c.is_ascii_digit()));
+            for operand in operands {
+                if always_quoted || operand.is_empty() || operand.contains([' ', ';', '"']) {
+                    write!(
+                        f,
+                        " \"{}\"",
+                        operand.replace('\\', "\\\\").replace('"', "\\\"")
+                    )?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub best_move: Move,
+    pub pv: Vec<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+pub fn analyse(
+    epd: &Epd,
+    limits: SearchLimits,
+    options: SearchOptions,
+    tt: &Arc<TranspositionTable>,
+) -> Option<Analysis> {
+    let board = epd.board()?;
+    let mut info = None;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut searcher = Searcher::with_tt(board, limits, options, stop, tt.clone());
+    let best_move = searcher.search(|latest| {
+        if latest.multipv == 1 {
+            info = Some((
+                latest.pv.clone(),
+                latest.score,
+                latest.depth,
+                latest.nodes,
+                latest.time,
+            ));
+        }
+    })?;
+    let (pv, score, depth, nodes, time) = info?;
+
+    Some(Analysis {
+        best_move,
+        pv,
+        score,
+        depth,
+        nodes,
+        time,
+    })
+}
+
+// Whether the analysis meets the position's `bm`, `am` and `dm` targets.
+// None if the position has no target that can be checked.
+pub fn is_solved(epd: &Epd, analysis: &Analysis) -> Option<bool> {
+    let mut solved = None;
+
+    if let Some(best) = epd.moves("bm") {
+        solved = Some(best.contains(&analysis.best_move));
+    }
+    if let Some(avoid) = epd.moves("am") {
+        solved = Some(solved.unwrap_or(true) && !avoid.contains(&analysis.best_move));
+    }
+    if let Some(moves) = epd
+        .operation("dm")
+        .and_then(|operands| operands.first()?.parse::<i32>().ok())
+    {
+        let mate = analysis.score >= MATE - MAX_PLY as i32 && MATE - analysis.score < 2 * moves;
+        solved = Some(solved.unwrap_or(true) && mate);
+    }
+    solved
+}
+
+// Records the analysis with the standard opcodes: depth, nodes, seconds,
+// evaluation, predicted move and variation.
+pub fn annotate(epd: &mut Epd, analysis: &Analysis) {
+    let Some(mut board) = epd.board() else {
+        return;
+    };
+
+    let mut pv = Vec::new();
+    for chess_move in &analysis.pv {
+        pv.push(to_san(&mut board, chess_move));
+        board.make_move(chess_move);
+    }
+
+    epd.set_operation("acd", vec![analysis.depth.to_string()]);
+    epd.set_operation("acn", vec![analysis.nodes.to_string()]);
+    epd.set_operation("acs", vec![analysis.time.as_secs().to_string()]);
+    epd.set_operation("ce", vec![analysis.score.to_string()]);
+    if let Some(predicted) = pv.first() {
+        epd.set_operation("pm", vec![predicted.clone()]);
+    }
+    epd.set_operation("pv", pv);
+}
+
+fn usage() {
+    println!(
+        "Usage: epd <file> [--depth N | --movetime MS | --nodes N] [--threads N] [--hash MB] \
+         [--annotate FILE]"
+    );
+}
+
+pub fn run(args: &[String]) {
+    let mut limits = SearchLimits::default();
+    let mut options = SearchOptions::default();
+    let mut path = None;
+    let mut annotate_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(String::as_str);
+
+        let valid = match arg.as_str() {
+            "--depth" => value().and_then(|value| value.parse().ok()).map(|value| {
+                limits.depth = Some(value);
+            }),
+            "--movetime" => value().and_then(|value| value.parse().ok()).map(|value| {
+                limits.movetime = Some(value);
+            }),
+            "--nodes" => value().and_then(|value| value.parse().ok()).map(|value| {
+                limits.nodes = Some(value);
+            }),
+            "--threads" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.threads = value;
+            }),
+            "--hash" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.hash = value;
+            }),
+            "--annotate" => value().map(|value| {
+                annotate_path = Some(value.to_string());
+            }),
+            _ if path.is_none() && !arg.starts_with("--") => {
+                path = Some(arg.clone());
+                Some(())
+            }
+            _ => None,
+        };
+
+        if valid.is_none() {
+            println!("Invalid argument {}", arg);
+            return usage();
+        }
+    }
+
+    let Some(path) = path else {
+        return usage();
+    };
+    if limits.depth.is_none() && limits.movetime.is_none() && limits.nodes.is_none() {
+        limits.movetime = Some(1000);
+    }
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) => return println!("Cannot read {}: {}", path, error),
+    };
+
+    let tt = Arc::new(TranspositionTable::new(options.hash));
+    let mut annotated = String::new();
+    let (mut solved, mut checked, mut total) = (0, 0, 0);
+
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(mut epd) = Epd::parse(line) else {
+            println!("Skipping line {}: not an EPD position", line_number + 1);
+            continue;
+        };
+
+        tt.clear();
+        total += 1;
+        let id = epd.id().map_or(format!("#{}", total), str::to_string);
+        let Some(analysis) = analyse(&epd, limits, options, &tt) else {
+            println!("{:<16} no legal moves", id);
+            continue;
+        };
+
+        let mut board = epd.board().unwrap();
+        let best_move = to_san(&mut board, &analysis.best_move);
+        let expected = ["bm", "am", "dm"]
+            .iter()
+            .filter_map(|opcode| {
+                let operands = epd.operation(opcode)?;
+                Some(format!("{} {}", opcode, operands.join(" ")))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let verdict = match is_solved(&epd, &analysis) {
+            Some(true) => {
+                solved += 1;
+                checked += 1;
+                "solved"
+            }
+            Some(false) => {
+                checked += 1;
+                "failed"
+            }
+            None => "-",
+        };
+        println!(
+            "{:<16} {:<8} {:<20} {:<6} depth {:>2} score {:>6} nodes {:>10} time {:.2}s",
+            id,
+            best_move,
+            expected,
+            verdict,
+            analysis.depth,
+            analysis.score,
+            analysis.nodes,
+            analysis.time.as_secs_f64()
+        );
+
+        if annotate_path.is_some() {
+            annotate(&mut epd, &analysis);
+            annotated.push_str(&epd.to_string());
+            annotated.push('\n');
+        }
+    }
+
+    println!("Solved {} of {} ({} positions)", solved, checked, total);
+    if let Some(annotate_path) = annotate_path {
+        if let Err(error) = fs::write(&annotate_path, annotated) {
+            println!("Cannot write {}: {}", annotate_path, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write_operations() {
+        let line = r#"2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001"; c0 "a; \"quoted\" comment";"#;
+        let epd = Epd::parse(line).unwrap();
+
+        assert_eq!(
+            epd.position,
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -"
+        );
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.operation("bm"), Some(&["Qg6".to_string()][..]));
+        assert_eq!(
+            epd.operation("c0"),
+            Some(&["a; \"quoted\" comment".to_string()][..])
+        );
+        assert_eq!(epd.to_string(), line);
+        assert_eq!(epd.moves("bm").unwrap()[0].to_uci(), "g3g6");
+
+        let full = Epd::parse("4k3/8/8/8/8/8/8/4K3 b - - 12 40 am Kd1 Kf1").unwrap();
+        assert_eq!(full.operation("am").unwrap().len(), 2);
+        assert!(full.moves("am").is_none());
+        let board = full.board().unwrap();
+        assert_eq!((board.halfmove_clock, board.fullmove_number), (12, 40));
+        assert_eq!(
+            Epd::from_board(&board).board().unwrap().to_fen(),
+            board.to_fen()
+        );
+
+        assert_eq!(Epd::parse("not a position"), None);
+    }
+
+    #[test]
+    fn solves_and_annotates_positions() {
+        let tt = Arc::new(TranspositionTable::new(1));
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+
+        let mut epd =
+            Epd::parse("6k1/5ppp/8/8/8/8/8/R3K3 w - - bm Ra8#; dm 1; id \"mate\";").unwrap();
+        let analysis = analyse(&epd, limits, SearchOptions::default(), &tt).unwrap();
+        assert_eq!(is_solved(&epd, &analysis), Some(true));
+
+        let mut avoid = epd.clone();
+        avoid.operations = vec![("am".into(), vec!["Ra8#".into()])];
+        assert_eq!(is_solved(&avoid, &analysis), Some(false));
+        avoid.operations.clear();
+        assert_eq!(is_solved(&avoid, &analysis), None);
+
+        annotate(&mut epd, &analysis);
+        assert_eq!(epd.operation("pm"), Some(&["Ra8#".to_string()][..]));
+        assert_eq!(epd.operation("acd"), Some(&["1".to_string()][..]));
+        assert!(epd.to_string().contains(" ce 30999; pm Ra8#; pv Ra8#;"));
+    }
+}
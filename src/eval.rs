@@ -0,0 +1,261 @@
+use crate::{
+    board::Board,
+    endgame::{self, Verdict},
+    piece::Color,
+};
+
+pub const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 0];
+
+// Piece-square tables from White's point of view, indexed like the board
+// (a8 first, h1 last).
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+const PIECE_TABLES: [&[i32; 64]; 6] = [
+    &PAWN_TABLE,
+    &KNIGHT_TABLE,
+    &BISHOP_TABLE,
+    &ROOK_TABLE,
+    &QUEEN_TABLE,
+    &KING_TABLE,
+];
+
+pub const TABLE_NAMES: [&str; 6] = [
+    "PAWN_TABLE",
+    "KNIGHT_TABLE",
+    "BISHOP_TABLE",
+    "ROOK_TABLE",
+    "QUEEN_TABLE",
+    "KING_TABLE",
+];
+
+// The classic evaluation as a parameter vector for tuning: the piece values
+// followed by the piece-square tables.
+pub const PARAMETER_COUNT: usize = 6 + 6 * 64;
+
+pub fn parameters() -> Vec<i32> {
+    PIECE_VALUES
+        .iter()
+        .chain(PIECE_TABLES.iter().flat_map(|table| table.iter()))
+        .copied()
+        .collect()
+}
+
+// The parameters each piece contributes, with coefficients from White's
+// point of view: the classic evaluation is their dot product with
+// `parameters()`.
+pub fn features(board: &Board) -> Vec<(usize, i32)> {
+    let mut features = Vec::new();
+
+    for (index, bitset) in board.pieces.iter().enumerate() {
+        let kind = index % 6;
+        let is_white = index < 6;
+
+        for square in bitset.set_bit_indices() {
+            let table_square = if is_white { square } else { square ^ 56 };
+            let sign = if is_white { 1 } else { -1 };
+
+            features.push((kind, sign));
+            features.push((6 + kind * 64 + table_square as usize, sign));
+        }
+    }
+
+    for (color_index, pocket) in board.pockets.iter().enumerate() {
+        let sign = if color_index == 0 { 1 } else { -1 };
+        for (kind, count) in pocket.iter().enumerate() {
+            if *count > 0 {
+                features.push((kind, sign * *count as i32));
+            }
+        }
+    }
+
+    features
+}
+
+// Material and piece-square tables, from White's point of view.
+fn classic(board: &Board) -> i32 {
+    let mut score = 0;
+
+    for (index, bitset) in board.pieces.iter().enumerate() {
+        let kind = index % 6;
+        let is_white = index < 6;
+
+        for square in bitset.set_bit_indices() {
+            let table_square = if is_white { square } else { square ^ 56 };
+            let value = PIECE_VALUES[kind] + PIECE_TABLES[kind][table_square as usize];
+
+            if is_white {
+                score += value;
+            } else {
+                score -= value;
+            }
+        }
+    }
+
+    // Pieces in hand count at their plain material value.
+    for (kind, value) in PIECE_VALUES.iter().enumerate().take(5) {
+        score += value * (board.pockets[0][kind] as i32 - board.pockets[1][kind] as i32);
+    }
+
+    score
+}
+
+// Static evaluation in centipawns, from the side to move's point of view.
+pub fn evaluate(board: &Board) -> i32 {
+    // The network scores from the side to move's point of view. A variant
+    // can replace both it and the classic evaluation.
+    let score = match (board.variant.evaluate(board), board.accumulators()) {
+        (Some(score), _) => score,
+        (None, Some(nnue)) => match board.side_to_move {
+            Color::White => nnue.evaluate(Color::White),
+            Color::Black => -nnue.evaluate(Color::Black),
+        },
+        (None, None) => classic(board),
+    };
+
+    // Known endings override or scale the general evaluation, under the
+    // standard rules only.
+    let verdict = if board.variant.is_standard() {
+        endgame::probe(board)
+    } else {
+        None
+    };
+    let score = match verdict {
+        Some(Verdict::Exact(exact)) => exact,
+        Some(Verdict::Scale(scale)) => score * scale / 64,
+        None => score,
+    } + board.variant.eval_bonus(board);
+
+    match board.side_to_move {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        board::STARTING_FEN_STRING,
+        nnue::{tests::test_network, Accumulators},
+    };
+
+    #[test]
+    fn starting_position_is_balanced() {
+        let board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+
+        assert_eq!(evaluate(&board), 0);
+    }
+
+    #[test]
+    fn evaluation_is_side_relative() {
+        let white = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        let black = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+
+        assert!(evaluate(&white) > 800);
+        assert_eq!(evaluate(&white), -evaluate(&black));
+    }
+
+    #[test]
+    fn network_replaces_classic_evaluation() {
+        let mut board = Board::from_fen("4k3/4p3/8/8/8/8/3P4/3QK3 b - - 0 1").unwrap();
+        let network = Arc::new(test_network(16));
+
+        board.set_network(Some(network.clone()));
+        let nnue = Accumulators::new(network, &board).evaluate(Color::Black);
+        assert_eq!(evaluate(&board), nnue);
+
+        board.set_network(None);
+        assert!(evaluate(&board) < -800);
+    }
+
+    #[test]
+    fn features_reproduce_classic_evaluation() {
+        let parameters = parameters();
+        assert_eq!(parameters.len(), PARAMETER_COUNT);
+
+        for fen in [
+            STARTING_FEN_STRING,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let score: i32 = features(&board)
+                .iter()
+                .map(|&(index, coefficient)| parameters[index] * coefficient)
+                .sum();
+            assert_eq!(score, classic(&board));
+        }
+    }
+}
@@ -1,14 +1,37 @@
 #![allow(dead_code)]
 
+mod arena;
+mod bench;
 mod bitset;
 mod board;
+mod book;
+mod datagen;
+mod endgame;
+mod epd;
+mod eval;
 mod movegen;
+mod movepick;
+mod nnue;
+mod pgn;
 mod piece;
+mod polyglot;
+mod retrograde;
+mod search;
+mod sprt;
+mod timeman;
+mod tournament;
+mod tt;
+mod tuner;
+mod uci;
+mod variant;
+mod xboard;
+mod zobrist;
 
 use bitset::Bitset;
-use board::{Board, CASTLING_FLAGS, STARTING_FEN_STRING};
-use movegen::{valid_moves, Move, MoveType};
+use board::Board;
+use movegen::{game_status, legal_moves, parse_uci_move, valid_moves, GameStatus};
 use piece::{Color, Piece};
+use variant::Variant;
 
 struct Game {
     pub board: Board,
@@ -33,22 +56,25 @@ impl Game {
 
         Self { board }
     }
-}
 
-fn pos_to_index(pos: &str) -> Option<(u8, u8)> {
-    if pos.len() == 2 {
-        let mut pos = pos.chars();
+    pub fn with_variant(variant: &'static dyn Variant) -> Self {
+        let board = Board::from_variant_fen(variant.start_fen(), variant).unwrap();
 
-        let file = pos.next().unwrap();
-        let rank = pos.next().unwrap().to_digit(10).unwrap() as u8;
+        Self { board }
+    }
+}
 
-        if ('a'..='h').contains(&file) && (1..=8).contains(&rank) {
-            Some((8 - rank, file as u8 - b'a'))
-        } else {
-            None
-        }
+fn pos_to_index(pos: &str) -> Option<(u8, u8)> {
+    let mut pos = pos.chars();
+    let (Some(file), Some(rank), None) = (pos.next(), pos.next(), pos.next()) else {
+        return None;
+    };
+    let rank = rank.to_digit(10)? as u8;
+
+    if ('a'..='h').contains(&file) && (1..=8).contains(&rank) {
+        Some((8 - rank, file as u8 - b'a'))
     } else {
-        panic!("Invalid position!")
+        None
     }
 }
 
@@ -75,68 +101,39 @@ fn bitset_to_pos(bitset: Bitset) -> Vec<String> {
     result
 }
 
-const CASTLING_MOVES: [((u8, u8), (u8, u8)); 4] = [
-    ((7, 4), (7, 6)),
-    ((7, 4), (7, 2)),
-    ((0, 4), (0, 6)),
-    ((0, 4), (0, 2)),
-];
-
-fn parse_move(board: &Board, from: (u8, u8), to: (u8, u8)) -> Move {
-    if let Some(piece) = board.get(from.0, from.1) {
-        let move_type = if CASTLING_MOVES.contains(&(from, to)) {
-            MoveType::Castling
-        } else if let Some(capture) = board.get(to.0, to.1) {
-            MoveType::Capture(capture)
-        } else {
-            MoveType::Normal
-        };
-
-        Move {
-            from,
-            to,
-            piece,
-            move_type,
-        }
-    } else {
-        panic!("Invalid piece at starting square!");
-    }
-}
-
-fn current_side_moves(board: &Board) -> Vec<Move> {
-    let mut current_moves = Vec::new();
-
-    let occupied = &board.occupied[board.side_to_move.index()];
-    for from_square in occupied.set_bit_indices() {
-        let from = (from_square / 8, from_square % 8);
-
-        let valid = valid_moves(board, &from);
-        for to_square in valid.set_bit_indices() {
-            let to = (to_square / 8, to_square % 8);
-
-            let chess_move = parse_move(board, from, to);
-            current_moves.push(chess_move);
+fn main() {
+    let args: Vec<_> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("uci") => return uci::run(),
+        Some("xboard") => return xboard::run(),
+        Some("book") => return book::run(&args[2..]),
+        Some("datagen") => return datagen::run(&args[2..]),
+        Some("tablebase") => return retrograde::run(&args[2..]),
+        Some("tune") => return tuner::run(&args[2..]),
+        Some("match") => return arena::run(&args[2..]),
+        Some("tournament") => return tournament::run(&args[2..]),
+        Some("epd") => return epd::run(&args[2..]),
+        Some("bench") => {
+            let depth = args.get(2).and_then(|depth| depth.parse().ok());
+            return bench::run(depth.unwrap_or(4));
         }
+        _ => {}
     }
 
-    current_moves
-}
-
-fn main() {
-    let mut game = Game::new();
+    let mut game = match args.get(1).map(String::as_str) {
+        Some("--variant") => match args.get(2).and_then(|name| variant::from_name(name)) {
+            Some(variant) => Game::with_variant(variant),
+            None => return println!("Unknown variant!"),
+        },
+        _ => Game::new(),
+    };
 
     loop {
+        println!("Variant: {}", game.board.variant.name());
+
         println!("Turn: {}", game.board.side_to_move);
 
-        print!("Castling: ");
-        for (index, flag) in game.board.can_castle.iter().enumerate() {
-            if *flag {
-                print!("{}", CASTLING_FLAGS[index]);
-            } else {
-                print!("-");
-            }
-        }
-        println!();
+        println!("Castling: {}", game.board.castling_field());
 
         println!("{}", game.board);
 
@@ -156,8 +153,14 @@ fn main() {
             println!("    {} - {}", color, bitset);
         }
 
+        let status = game_status(&mut game.board);
+        if status != GameStatus::Ongoing {
+            println!("Game over: {:?}", status);
+            return;
+        }
+
         const MAX_MOVES: usize = 50;
-        let current_side_moves = current_side_moves(&game.board);
+        let current_side_moves = legal_moves(&mut game.board);
         let current_side_moves: Vec<_> = current_side_moves
             .iter()
             .map(|m| m.to_string())
@@ -195,18 +198,13 @@ fn main() {
                 }
 
                 continue 'inner;
-            } else if chess_move.len() == 4 {
-                let start_square = &chess_move[0..2];
-                let end_square = &chess_move[2..];
-
-                let from = pos_to_index(start_square);
-                let to = pos_to_index(end_square);
-
-                if let (Some(from), Some(to)) = (from, to) {
-                    let chess_move = parse_move(&game.board, from, to);
-                    game.board.make_move(&chess_move);
-                } else {
-                    panic!("Out of bounds position!");
+            } else if chess_move.len() == 4 || chess_move.len() == 5 {
+                match parse_uci_move(&mut game.board, chess_move) {
+                    Some(chess_move) => game.board.make_move(&chess_move),
+                    None => {
+                        println!("Not a valid move!");
+                        continue 'inner;
+                    }
                 }
             } else {
                 println!("Wrong move syntax!");
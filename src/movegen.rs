@@ -5,15 +5,22 @@ use crate::{
     board::Board,
     index_to_pos,
     piece::{Color, Piece},
+    pos_to_index,
 };
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MoveType {
     Normal,
     Capture(Piece),
+    EnPassant,
+    Promotion(Piece, Option<Piece>),
     Castling,
+    // A piece from the pocket put on an empty square; its from square is the
+    // target square.
+    Drop,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Move {
     pub from: (u8, u8),
     pub to: (u8, u8),
@@ -21,6 +28,57 @@ pub struct Move {
     pub move_type: MoveType,
 }
 
+impl Move {
+    pub fn is_capture(&self) -> bool {
+        matches!(
+            self.move_type,
+            MoveType::Capture(_) | MoveType::EnPassant | MoveType::Promotion(_, Some(_))
+        )
+    }
+
+    pub fn captured(&self) -> Option<Piece> {
+        match self.move_type {
+            MoveType::Capture(capture) => Some(capture),
+            MoveType::EnPassant => Piece::from_kind(0, self.piece.color().opposite()),
+            MoveType::Promotion(_, capture) => capture,
+            _ => None,
+        }
+    }
+
+    pub fn is_noisy(&self) -> bool {
+        self.is_capture() || self.promotion().is_some()
+    }
+
+    pub fn promotion(&self) -> Option<Piece> {
+        match self.move_type {
+            MoveType::Promotion(promotion, _) => Some(promotion),
+            _ => None,
+        }
+    }
+
+    pub fn to_uci(self) -> String {
+        if self.move_type == MoveType::Drop {
+            return format!(
+                "{}@{}",
+                self.piece.simple_char().to_ascii_uppercase(),
+                index_to_pos(self.to).unwrap()
+            );
+        }
+
+        let mut uci = format!(
+            "{}{}",
+            index_to_pos(self.from).unwrap(),
+            index_to_pos(self.to).unwrap()
+        );
+
+        if let Some(promotion) = self.promotion() {
+            uci.push(promotion.simple_char().to_ascii_lowercase());
+        }
+
+        uci
+    }
+}
+
 impl Display for Move {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Move {
@@ -35,19 +93,27 @@ impl Display for Move {
 
         match move_type {
             MoveType::Normal => {
-                if *piece == Piece::WhitePawn || *piece == Piece::BlackPawn {
+                if piece.is_pawn() {
                     write!(f, "{}", to_pos)
                 } else {
                     write!(f, "{}{}", piece, to_pos)
                 }
             }
-            MoveType::Capture(_) => {
-                if *piece == Piece::WhitePawn || *piece == Piece::BlackPawn {
+            MoveType::Capture(_) | MoveType::EnPassant => {
+                if piece.is_pawn() {
                     write!(f, "{}x{}", &from_pos[..1], to_pos)
                 } else {
                     write!(f, "{}x{}", piece, to_pos)
                 }
             }
+            MoveType::Promotion(promotion, capture) => {
+                let promotion = promotion.simple_char().to_ascii_uppercase();
+                if capture.is_some() {
+                    write!(f, "{}x{}={}", &from_pos[..1], to_pos, promotion)
+                } else {
+                    write!(f, "{}={}", to_pos, promotion)
+                }
+            }
             MoveType::Castling => {
                 if to.1 == 6 {
                     write!(f, "O-O")
@@ -55,6 +121,7 @@ impl Display for Move {
                     write!(f, "O-O-O")
                 }
             }
+            MoveType::Drop => write!(f, "{}@{}", piece, to_pos),
         }
     }
 }
@@ -75,51 +142,50 @@ fn valid_target(board: &Board, moves: &mut Bitset, target_square: u8) -> bool {
     true
 }
 
-const PAWN_OFFSETS: [(i8, i8); 4] = [(1, -1), (1, 0), (1, 1), (2, 0)];
-
 fn pawn_moves(board: &Board, from: &(u8, u8)) -> Bitset {
     let mut moves = Bitset::new(0);
 
     let (rank, file) = *from;
 
-    let offsets = match board.side_to_move {
-        Color::White => if rank == 6 {
-            &PAWN_OFFSETS
-        } else {
-            &PAWN_OFFSETS[..3]
-        }
-        .iter()
-        .map(|(r, f)| (-r, *f))
-        .collect(),
-        Color::Black => if rank == 1 {
-            &PAWN_OFFSETS
-        } else {
-            &PAWN_OFFSETS[..3]
-        }
-        .to_vec(),
+    let direction = match board.side_to_move {
+        Color::White => -1,
+        Color::Black => 1,
     };
+    let start_ranks = board.variant.double_push_ranks(board.side_to_move);
 
-    for (dr, df) in offsets {
-        let (r, f) = (rank as i8 + dr, file as i8 + df);
+    let r = rank as i8 + direction;
+    if !(0..8).contains(&r) {
+        return moves;
+    }
 
-        if (0..8).contains(&r) && (0..8).contains(&f) {
-            let target_square = (r * 8 + f) as u8;
-            if df == 0 {
-                let occupied = &board.occupied[0];
-                if occupied.is_bit_set(target_square) {
-                    break;
-                }
+    let occupied = &board.occupied[0];
+    let target_square = r as u8 * 8 + file;
+    if !occupied.is_bit_set(target_square) {
+        moves.set_bit(target_square);
 
-                moves.set_bit(target_square);
-            } else {
-                let opposide_occupied = &board.occupied[board.side_to_move.opposite().index()];
-                if opposide_occupied.is_bit_set(target_square) {
-                    moves.set_bit(target_square);
-                }
+        if start_ranks.contains(&rank) {
+            let double_target_square = (r + direction) as u8 * 8 + file;
+            if !occupied.is_bit_set(double_target_square) {
+                moves.set_bit(double_target_square);
             }
         }
     }
 
+    let opposide_occupied = &board.occupied[board.side_to_move.opposite().index()];
+    for df in [-1, 1] {
+        let f = file as i8 + df;
+        if !(0..8).contains(&f) {
+            continue;
+        }
+
+        let target_square = r as u8 * 8 + f as u8;
+        if opposide_occupied.is_bit_set(target_square)
+            || board.en_passant == Some((r as u8, f as u8))
+        {
+            moves.set_bit(target_square);
+        }
+    }
+
     moves
 }
 
@@ -175,12 +241,15 @@ fn sliding_moves(board: &Board, from: &(u8, u8), directions: &[(i8, i8)]) -> Bit
     moves
 }
 
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
 fn bishop_moves(board: &Board, from: &(u8, u8)) -> Bitset {
-    sliding_moves(board, from, &[(1, 1), (1, -1), (-1, 1), (-1, -1)])
+    sliding_moves(board, from, &BISHOP_DIRECTIONS)
 }
 
 fn rook_moves(board: &Board, from: &(u8, u8)) -> Bitset {
-    sliding_moves(board, from, &[(1, 0), (-1, 0), (0, 1), (0, -1)])
+    sliding_moves(board, from, &ROOK_DIRECTIONS)
 }
 
 fn queen_moves(board: &Board, from: &(u8, u8)) -> Bitset {
@@ -225,53 +294,96 @@ fn is_empty(board: &Board, indices: &[(u8, u8)]) -> bool {
         .unwrap_or(false)
 }
 
-fn castling_moves(board: &Board, from: &(u8, u8)) -> Bitset {
-    let mut moves = Bitset::new(0);
+fn is_safe(board: &Board, indices: &[(u8, u8)]) -> bool {
+    let attacker = board.side_to_move.opposite();
+    indices
+        .iter()
+        .all(|square| !is_attacked(board, *square, attacker))
+}
 
-    let king_pos = match board.side_to_move {
-        Color::White => (7, 4),
-        Color::Black => (0, 4),
+// Castling with the king and rook anywhere on the back rank, as in Chess960.
+// Every square either piece crosses must be empty apart from the two of them,
+// and the king mustn't pass through check.
+pub fn castling_moves(board: &Board) -> Vec<Move> {
+    let mut moves = Vec::new();
+    if !board.variant.allows_castling() {
+        return moves;
+    }
+
+    let color = board.side_to_move;
+    let rank = match color {
+        Color::White => 7,
+        Color::Black => 0,
+    };
+    let Some(from) = board.king_square(color).filter(|square| square.0 == rank) else {
+        return moves;
     };
+    let king = Piece::from_kind(5, color).unwrap();
+    let rook = Piece::from_kind(3, color).unwrap();
 
-    if *from == king_pos {
-        let (king_castle_index, queen_castle_index) = (
-            2 * board.side_to_move.index() - 2,
-            2 * board.side_to_move.index() - 1,
-        );
+    for (king_file, rook_file, index) in
+        [(6, 5, 2 * color.index() - 2), (2, 3, 2 * color.index() - 1)]
+    {
+        let rook_from = board.castling_rooks[index];
+        if !board.can_castle[index] || board.get(rook_from.0, rook_from.1) != Some(rook) {
+            continue;
+        }
 
-        let king_rook = board.get(king_pos.0, 7);
-        if let Some(king_rook) = king_rook {
-            if (king_rook == Piece::WhiteRook || king_rook == Piece::BlackRook)
-                && king_rook.color() == board.side_to_move
-                && board.can_castle[king_castle_index]
-                && is_empty(board, &[(king_pos.0, 5), (king_pos.0, 6)])
-            {
-                let square = king_pos.0 * 8 + 6;
-                moves.set_bit(square);
-            }
+        let span = |a: u8, b: u8| a.min(b)..=a.max(b);
+        let crossed = span(from.1, king_file).chain(span(rook_from.1, rook_file));
+        let blocked = crossed
+            .filter(|file| *file != from.1 && *file != rook_from.1)
+            .any(|file| board.get(rank, file).is_some());
+
+        let king_path: Vec<_> = span(from.1, king_file).map(|file| (rank, file)).collect();
+        if !blocked && is_safe(board, &king_path) {
+            moves.push(Move {
+                from,
+                to: (rank, king_file),
+                piece: king,
+                move_type: MoveType::Castling,
+            });
+        }
+    }
+
+    moves
+}
+
+// Pieces from the pocket dropped on empty squares, pawns not on the first or
+// last rank.
+pub fn drop_moves(board: &Board) -> Vec<Move> {
+    let mut moves = Vec::new();
+
+    let color = board.side_to_move;
+    for (kind, count) in board.pockets[color.index() - 1].iter().enumerate() {
+        if *count == 0 {
+            continue;
         }
 
-        let queen_rook = board.get(king_pos.0, 0);
-        if let Some(queen_rook) = queen_rook {
-            if (queen_rook == Piece::WhiteRook || queen_rook == Piece::BlackRook)
-                && queen_rook.color() == board.side_to_move
-                && board.can_castle[queen_castle_index]
-                && is_empty(board, &[(king_pos.0, 1), (king_pos.0, 2), (king_pos.0, 3)])
-            {
-                let square = king_pos.0 * 8 + 2;
-                moves.set_bit(square);
+        let piece = Piece::from_kind(kind, color).unwrap();
+        for square in 0..64 {
+            let to = (square / 8, square % 8);
+            if board.occupied[0].is_bit_set(square) || (kind == 0 && (to.0 == 0 || to.0 == 7)) {
+                continue;
             }
+
+            moves.push(Move {
+                from: to,
+                to,
+                piece,
+                move_type: MoveType::Drop,
+            });
         }
     }
 
     moves
 }
 
-pub fn valid_moves(board: &Board, from: &(u8, u8)) -> Bitset {
+// Moves of every piece but castling.
+fn piece_moves(board: &Board, from: &(u8, u8)) -> Bitset {
     let piece = board.get(from.0, from.1);
     if let Some(piece) = piece {
         if piece.color() != board.side_to_move {
-            println!("Out of turn move!");
             return Bitset::new(0);
         }
 
@@ -282,7 +394,14 @@ pub fn valid_moves(board: &Board, from: &(u8, u8)) -> Bitset {
             Piece::WhiteRook | Piece::BlackRook => rook_moves(board, from),
             Piece::WhiteQueen | Piece::BlackQueen => queen_moves(board, from),
             Piece::WhiteKing | Piece::BlackKing => {
-                king_moves(board, from) | castling_moves(board, from)
+                let mut moves = king_moves(board, from);
+                if !board.variant.king_captures() {
+                    let enemies = &board.occupied[board.side_to_move.opposite().index()];
+                    for square in enemies.set_bit_indices() {
+                        moves.clear_bit(square);
+                    }
+                }
+                moves
             }
         };
     }
@@ -290,10 +409,657 @@ pub fn valid_moves(board: &Board, from: &(u8, u8)) -> Bitset {
     Bitset::new(0)
 }
 
+pub fn valid_moves(board: &Board, from: &(u8, u8)) -> Bitset {
+    let mut moves = piece_moves(board, from);
+    for castling in castling_moves(board) {
+        if castling.from == *from {
+            moves.set_bit(castling.to.0 * 8 + castling.to.1);
+        }
+    }
+
+    moves
+}
+
+fn piece_at_offset(board: &Board, square: (u8, u8), offset: (i8, i8)) -> Option<Piece> {
+    let (r, f) = (square.0 as i8 + offset.0, square.1 as i8 + offset.1);
+    if (0..8).contains(&r) && (0..8).contains(&f) {
+        board.get(r as u8, f as u8)
+    } else {
+        None
+    }
+}
+
+fn slider_at(board: &Board, square: (u8, u8), direction: (i8, i8)) -> Option<Piece> {
+    let (mut r, mut f) = (square.0 as i8, square.1 as i8);
+
+    while (0..8).contains(&(r + direction.0)) && (0..8).contains(&(f + direction.1)) {
+        r += direction.0;
+        f += direction.1;
+
+        if let Some(piece) = board.get(r as u8, f as u8) {
+            return Some(piece);
+        }
+    }
+
+    None
+}
+
+pub fn is_attacked(board: &Board, square: (u8, u8), by: Color) -> bool {
+    let pawn_rank = match by {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    let pawn = Piece::from_kind(0, by);
+    if [(pawn_rank, -1), (pawn_rank, 1)]
+        .iter()
+        .any(|offset| piece_at_offset(board, square, *offset) == pawn)
+    {
+        return true;
+    }
+
+    let knight = Piece::from_kind(1, by);
+    if KNIGHT_OFFSETS
+        .iter()
+        .any(|offset| piece_at_offset(board, square, *offset) == knight)
+    {
+        return true;
+    }
+
+    // Kings that can't capture attack nothing.
+    let king = Piece::from_kind(5, by);
+    if board.variant.king_captures()
+        && KING_OFFSETS
+            .iter()
+            .any(|offset| piece_at_offset(board, square, *offset) == king)
+    {
+        return true;
+    }
+
+    let queen = Piece::from_kind(4, by);
+
+    let bishop = Piece::from_kind(2, by);
+    if BISHOP_DIRECTIONS.iter().any(|direction| {
+        let piece = slider_at(board, square, *direction);
+        piece.is_some() && (piece == bishop || piece == queen)
+    }) {
+        return true;
+    }
+
+    let rook = Piece::from_kind(3, by);
+    ROOK_DIRECTIONS.iter().any(|direction| {
+        let piece = slider_at(board, square, *direction);
+        piece.is_some() && (piece == rook || piece == queen)
+    })
+}
+
+pub fn in_check(board: &Board, color: Color) -> bool {
+    board.variant.in_check(board, color)
+}
+
+// Check under the standard rules.
+pub fn king_in_check(board: &Board, color: Color) -> bool {
+    match board.king_square(color) {
+        Some(king_square) => is_attacked(board, king_square, color.opposite()),
+        None => false,
+    }
+}
+
+fn square_at_offset(square: (u8, u8), offset: (i8, i8)) -> Option<(u8, u8)> {
+    let (r, f) = (square.0 as i8 + offset.0, square.1 as i8 + offset.1);
+    if (0..8).contains(&r) && (0..8).contains(&f) {
+        Some((r as u8, f as u8))
+    } else {
+        None
+    }
+}
+
+fn slider_square_at(board: &Board, square: (u8, u8), direction: (i8, i8)) -> Option<(u8, u8)> {
+    let mut current = square;
+    while let Some(next) = square_at_offset(current, direction) {
+        if board.get(next.0, next.1).is_some() {
+            return Some(next);
+        }
+        current = next;
+    }
+
+    None
+}
+
+// The cheapest piece of the given color attacking a square, used to play out
+// capture sequences.
+pub fn least_valuable_attacker(board: &Board, square: (u8, u8), by: Color) -> Option<(u8, u8)> {
+    let is_piece = |attacker: Option<(u8, u8)>, kinds: &[usize]| {
+        attacker.filter(|(r, f)| {
+            board
+                .get(*r, *f)
+                .is_some_and(|piece| piece.color() == by && kinds.contains(&piece.kind()))
+        })
+    };
+
+    let pawn_rank = match by {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    let leapers = |offsets: &[(i8, i8)], kind: usize| {
+        offsets
+            .iter()
+            .find_map(|offset| is_piece(square_at_offset(square, *offset), &[kind]))
+    };
+    let sliders = |directions: &[(i8, i8)], kinds: &[usize]| {
+        directions
+            .iter()
+            .find_map(|direction| is_piece(slider_square_at(board, square, *direction), kinds))
+    };
+
+    leapers(&[(pawn_rank, -1), (pawn_rank, 1)], 0)
+        .or_else(|| leapers(&KNIGHT_OFFSETS, 1))
+        .or_else(|| sliders(&BISHOP_DIRECTIONS, &[2]))
+        .or_else(|| sliders(&ROOK_DIRECTIONS, &[3]))
+        .or_else(|| sliders(&BISHOP_DIRECTIONS, &[4]).or_else(|| sliders(&ROOK_DIRECTIONS, &[4])))
+        .or_else(|| leapers(&KING_OFFSETS, 5))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenType {
+    All,
+    Noisy,
+    Quiet,
+}
+
+fn push_moves(board: &Board, from: (u8, u8), to: (u8, u8), piece: Piece, moves: &mut Vec<Move>) {
+    let capture = board.get(to.0, to.1);
+
+    if piece.is_pawn() && (to.0 == 0 || to.0 == 7) {
+        for &kind in board.variant.promotion_kinds() {
+            let promotion = Piece::from_kind(kind, board.side_to_move).unwrap();
+            moves.push(Move {
+                from,
+                to,
+                piece,
+                move_type: MoveType::Promotion(promotion, capture),
+            });
+        }
+        return;
+    }
+
+    let move_type = if let Some(capture) = capture {
+        MoveType::Capture(capture)
+    } else if piece.is_pawn() && from.1 != to.1 {
+        MoveType::EnPassant
+    } else {
+        MoveType::Normal
+    };
+
+    moves.push(Move {
+        from,
+        to,
+        piece,
+        move_type,
+    });
+}
+
+pub fn generate_moves(board: &Board, gen_type: GenType) -> Vec<Move> {
+    let mut moves = unfiltered_moves(board, gen_type);
+    board.variant.filter_moves(board, &mut moves);
+    moves
+}
+
+// The moves the pieces can make before the variant rules anything out.
+fn unfiltered_moves(board: &Board, gen_type: GenType) -> Vec<Move> {
+    let mut moves = Vec::new();
+
+    let opposite_occupied = &board.occupied[board.side_to_move.opposite().index()];
+
+    let occupied = &board.occupied[board.side_to_move.index()];
+    for from_square in occupied.set_bit_indices() {
+        let from = (from_square / 8, from_square % 8);
+        let piece = board.get(from.0, from.1).unwrap();
+
+        let valid = piece_moves(board, &from);
+        for to_square in valid.set_bit_indices() {
+            let to = (to_square / 8, to_square % 8);
+
+            let noisy = opposite_occupied.is_bit_set(to_square)
+                || (piece.is_pawn() && (to.0 == 0 || to.0 == 7 || from.1 != to.1));
+            let wanted = match gen_type {
+                GenType::All => true,
+                GenType::Noisy => noisy,
+                GenType::Quiet => !noisy,
+            };
+
+            if wanted {
+                push_moves(board, from, to, piece, &mut moves);
+            }
+        }
+    }
+
+    if gen_type != GenType::Noisy {
+        moves.extend(castling_moves(board));
+        if board.variant.has_drops() {
+            moves.extend(drop_moves(board));
+        }
+    }
+
+    moves
+}
+
+// Whether the side to move has a capture, whatever the variant allows.
+pub fn has_capture(board: &Board) -> bool {
+    unfiltered_moves(board, GenType::Noisy)
+        .iter()
+        .any(Move::is_capture)
+}
+
+pub fn pseudo_legal_moves(board: &Board) -> Vec<Move> {
+    generate_moves(board, GenType::All)
+}
+
+// Checks that a move from another position (hash table, killer slot) can be
+// played here.
+pub fn is_pseudo_legal(board: &Board, chess_move: &Move) -> bool {
+    let Move {
+        from, to, piece, ..
+    } = *chess_move;
+    let possible = if chess_move.move_type == MoveType::Drop {
+        board.variant.has_drops() && drop_moves(board).contains(chess_move)
+    } else if board.get(from.0, from.1) != Some(piece) {
+        false
+    } else if chess_move.move_type == MoveType::Castling {
+        castling_moves(board).contains(chess_move)
+    } else if !piece_moves(board, &from).is_bit_set(to.0 * 8 + to.1) {
+        false
+    } else {
+        let mut moves = Vec::new();
+        push_moves(board, from, to, piece, &mut moves);
+        moves.contains(chess_move)
+    };
+
+    if !possible {
+        return false;
+    }
+
+    // The variant may still forbid it, like a quiet move when antichess has a
+    // capture.
+    let mut moves = vec![*chess_move];
+    board.variant.filter_moves(board, &mut moves);
+    !moves.is_empty()
+}
+
+// Whether the position a move by `color` led to breaks the rules, usually by
+// leaving its own king in check.
+pub fn is_illegal_position(board: &Board, color: Color) -> bool {
+    board.variant.is_illegal_position(board, color)
+}
+
+pub fn legal_moves(board: &mut Board) -> Vec<Move> {
+    let color = board.side_to_move;
+
+    pseudo_legal_moves(board)
+        .into_iter()
+        .filter(|chess_move| {
+            board.make_move(chess_move);
+            let legal = !is_illegal_position(board, color);
+            board.unmake_move(chess_move);
+            legal
+        })
+        .collect()
+}
+
+// The position before a quiet move was played, with the mover to move again.
+pub fn retract(board: &Board, unmove: &Move) -> Board {
+    let mut previous = board.clone();
+    previous.clear(unmove.to.0, unmove.to.1, unmove.piece);
+    previous.set(unmove.from.0, unmove.from.1, unmove.piece);
+    previous.side_to_move = board.side_to_move.opposite();
+    previous
+}
+
+// Moves the side that just moved could have played to reach this position.
+// Only quiet moves are retracted: captures, promotions, castling and en
+// passant change more than one square and are left out.
+pub fn unmoves(board: &Board) -> Vec<Move> {
+    let color = board.side_to_move.opposite();
+    let mut unmoves = Vec::new();
+
+    for kind in 0..6 {
+        let piece = Piece::from_kind(kind, color).unwrap();
+
+        for square in board.pieces[piece.index()].set_bit_indices() {
+            let to = (square / 8, square % 8);
+            let is_empty = |square: &(u8, u8)| board.get(square.0, square.1).is_none();
+
+            let origins: Vec<_> = match kind {
+                0 => {
+                    // Pawns step back towards their own side, and two squares
+                    // back to their starting rank.
+                    let (direction, double_rank, start_rank) = match color {
+                        Color::White => (1, 4, 6),
+                        Color::Black => (-1, 3, 1),
+                    };
+                    let single = square_at_offset(to, (direction, 0))
+                        .filter(|from| is_empty(from) && from.0 != 0 && from.0 != 7);
+                    let double = single
+                        .filter(|_| to.0 == double_rank)
+                        .and_then(|from| square_at_offset(from, (direction, 0)))
+                        .filter(|from| is_empty(from) && from.0 == start_rank);
+                    single.into_iter().chain(double).collect()
+                }
+                1 | 5 => {
+                    let offsets = if kind == 1 {
+                        KNIGHT_OFFSETS
+                    } else {
+                        KING_OFFSETS
+                    };
+                    offsets
+                        .iter()
+                        .filter_map(|offset| square_at_offset(to, *offset))
+                        .filter(is_empty)
+                        .collect()
+                }
+                _ => {
+                    let directions = match kind {
+                        2 => BISHOP_DIRECTIONS.to_vec(),
+                        3 => ROOK_DIRECTIONS.to_vec(),
+                        _ => [BISHOP_DIRECTIONS, ROOK_DIRECTIONS].concat(),
+                    };
+                    let mut origins = Vec::new();
+                    for direction in directions {
+                        let mut current = to;
+                        while let Some(next) = square_at_offset(current, direction).filter(is_empty)
+                        {
+                            origins.push(next);
+                            current = next;
+                        }
+                    }
+                    origins
+                }
+            };
+
+            for from in origins {
+                let unmove = Move {
+                    from,
+                    to,
+                    piece,
+                    move_type: MoveType::Normal,
+                };
+
+                // The side to move now can't have been left in check.
+                if !in_check(&retract(board, &unmove), board.side_to_move) {
+                    unmoves.push(unmove);
+                }
+            }
+        }
+    }
+
+    unmoves
+}
+
+pub fn parse_uci_move(board: &mut Board, uci: &str) -> Option<Move> {
+    if !(4..=5).contains(&uci.len()) || !uci.is_ascii() {
+        return None;
+    }
+
+    // Drops are written with the piece letter, as in "N@f3".
+    if let Some((piece, square)) = uci.split_once('@') {
+        if piece.len() != 1 || square.len() != 2 {
+            return None;
+        }
+        let to = pos_to_index(square)?;
+        let kind = Piece::from_char(piece.chars().next()?)?.kind();
+        return legal_moves(board).into_iter().find(|chess_move| {
+            chess_move.move_type == MoveType::Drop
+                && chess_move.to == to
+                && chess_move.piece.kind() == kind
+        });
+    }
+
+    let from = pos_to_index(&uci[0..2])?;
+    let to = pos_to_index(&uci[2..4])?;
+    let promotion = uci[4..].chars().next();
+
+    let moves = legal_moves(board);
+    let normal = moves.iter().find(|chess_move| {
+        chess_move.move_type != MoveType::Castling
+            && chess_move.from == from
+            && chess_move.to == to
+            && chess_move
+                .promotion()
+                .map(|piece| piece.simple_char().to_ascii_lowercase())
+                == promotion
+    });
+
+    // Castling is either the king's two-square move or, as in Chess960, the
+    // king taking its own rook.
+    normal.copied().or_else(|| {
+        moves.into_iter().find(|chess_move| {
+            chess_move.move_type == MoveType::Castling
+                && chess_move.from == from
+                && (chess_move.to == to || board.castling_rook_squares(chess_move.to).0 == to)
+                && promotion.is_none()
+        })
+    })
+}
+
+// Writes a move in UCI notation. Chess960 castling is written as the king
+// taking its own rook.
+pub fn format_uci(board: &Board, chess_move: &Move, chess960: bool) -> String {
+    if chess960 && chess_move.move_type == MoveType::Castling {
+        let color = chess_move.piece.color();
+        let king_side = chess_move.to.1 == 6;
+        let index = 2 * color.index() - 1 - king_side as usize;
+        let rook = board.castling_rooks[index];
+        format!(
+            "{}{}",
+            index_to_pos(chess_move.from).unwrap(),
+            index_to_pos(rook).unwrap()
+        )
+    } else {
+        chess_move.to_uci()
+    }
+}
+
+// Parses standard algebraic notation, tolerating check marks, annotations
+// and promotions written without '='.
+pub fn parse_san(board: &mut Board, san: &str) -> Option<Move> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    if !san.is_ascii() {
+        return None;
+    }
+
+    let castle_file = match san {
+        "O-O" | "0-0" => Some(6),
+        "O-O-O" | "0-0-0" => Some(2),
+        _ => None,
+    };
+    if let Some(file) = castle_file {
+        return legal_moves(board).into_iter().find(|chess_move| {
+            chess_move.move_type == MoveType::Castling && chess_move.to.1 == file
+        });
+    }
+
+    if let Some((piece, square)) = san.split_once('@') {
+        if piece.len() > 1 || square.len() != 2 {
+            return None;
+        }
+        let kind = match piece {
+            "" | "P" => 0,
+            _ => Piece::from_char(piece.chars().next()?)?.kind(),
+        };
+        let to = pos_to_index(square)?;
+        return legal_moves(board).into_iter().find(|chess_move| {
+            chess_move.move_type == MoveType::Drop
+                && chess_move.to == to
+                && chess_move.piece.kind() == kind
+        });
+    }
+
+    let (san, promotion) = match san.as_bytes().last()? {
+        b'N' | b'B' | b'R' | b'Q' | b'K' => {
+            let promotion = san.chars().last();
+            (san[..san.len() - 1].trim_end_matches('='), promotion)
+        }
+        _ => (san, None),
+    };
+
+    let (kind, san) = match san.chars().next()? {
+        'N' => (1, &san[1..]),
+        'B' => (2, &san[1..]),
+        'R' => (3, &san[1..]),
+        'Q' => (4, &san[1..]),
+        'K' => (5, &san[1..]),
+        _ => (0, san),
+    };
+
+    let san = san.replace('x', "");
+    if san.len() < 2 {
+        return None;
+    }
+    let to = pos_to_index(&san[san.len() - 2..])?;
+    let disambiguation = &san[..san.len() - 2];
+
+    let mut candidates = legal_moves(board).into_iter().filter(|chess_move| {
+        let from = index_to_pos(chess_move.from).unwrap();
+
+        chess_move.piece.kind() == kind
+            && chess_move.to == to
+            && chess_move.move_type != MoveType::Castling
+            && chess_move.move_type != MoveType::Drop
+            && chess_move
+                .promotion()
+                .map(|piece| piece.simple_char().to_ascii_uppercase())
+                == promotion
+            && disambiguation.chars().all(|c| from.contains(c))
+    });
+
+    let chess_move = candidates.next()?;
+    candidates.next().is_none().then_some(chess_move)
+}
+
+// Writes a legal move in standard algebraic notation, with the file or rank
+// of origin only where another piece of the same kind could also move there.
+pub fn to_san(board: &mut Board, chess_move: &Move) -> String {
+    let mut san = if chess_move.move_type == MoveType::Castling {
+        if chess_move.to.1 == 6 { "O-O" } else { "O-O-O" }.to_string()
+    } else if chess_move.move_type == MoveType::Drop {
+        chess_move.to_uci()
+    } else {
+        let from = index_to_pos(chess_move.from).unwrap();
+        let to = index_to_pos(chess_move.to).unwrap();
+        let mut san = String::new();
+
+        if chess_move.piece.kind() == 0 {
+            if chess_move.is_capture() {
+                san.push_str(&from[..1]);
+            }
+        } else {
+            san.push(chess_move.piece.simple_char().to_ascii_uppercase());
+
+            let rivals: Vec<_> = legal_moves(board)
+                .into_iter()
+                .filter(|other| {
+                    other.piece == chess_move.piece
+                        && other.to == chess_move.to
+                        && other.from != chess_move.from
+                        && other.move_type != MoveType::Drop
+                })
+                .collect();
+            if !rivals.is_empty() {
+                if rivals.iter().all(|other| other.from.1 != chess_move.from.1) {
+                    san.push_str(&from[..1]);
+                } else if rivals.iter().all(|other| other.from.0 != chess_move.from.0) {
+                    san.push_str(&from[1..]);
+                } else {
+                    san.push_str(&from);
+                }
+            }
+        }
+
+        if chess_move.is_capture() {
+            san.push('x');
+        }
+        san.push_str(&to);
+
+        if let Some(promotion) = chess_move.promotion() {
+            san.push('=');
+            san.push(promotion.simple_char().to_ascii_uppercase());
+        }
+        san
+    };
+
+    board.make_move(chess_move);
+    if in_check(board, board.side_to_move) {
+        san.push(if legal_moves(board).is_empty() {
+            '#'
+        } else {
+            '+'
+        });
+    }
+    board.unmake_move(chess_move);
+
+    san
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    Ongoing,
+    Checkmate(Color),
+    Stalemate,
+    FiftyMoveRule,
+    Repetition,
+    // Won or drawn by a variant's own rule, such as the third check.
+    VariantWin(Color),
+    VariantDraw,
+}
+
+// Games decided by a variant's own rule rather than by mate, checked before
+// the side to move gets to play.
+pub fn variant_status(board: &mut Board) -> Option<GameStatus> {
+    let variant = board.variant;
+    variant.status(board)
+}
+
+pub fn game_status(board: &mut Board) -> GameStatus {
+    if let Some(status) = variant_status(board) {
+        status
+    } else if legal_moves(board).is_empty() {
+        board.variant.no_moves_status(board)
+    } else if board.is_fifty_move_draw() {
+        GameStatus::FiftyMoveRule
+    } else if board.repetition_count() >= 2 {
+        GameStatus::Repetition
+    } else {
+        GameStatus::Ongoing
+    }
+}
+
+pub fn perft(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for chess_move in moves {
+        board.make_move(&chess_move);
+        nodes += perft(board, depth - 1);
+        board.unmake_move(&chess_move);
+    }
+
+    nodes
+}
+
 // TODO: Write rest of tests.
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        board::STARTING_FEN_STRING,
+        variant::{Antichess, Atomic, Horde, KingOfTheHill, RacingKings, Variant},
+    };
 
     #[test]
     fn bishop_empty_board() {
@@ -331,6 +1097,360 @@ mod tests {
         );
     }
 
+    #[test]
+    fn pawn_blocked_still_captures() {
+        let mut board = Board::new();
+        board.set(6, 4, Piece::WhitePawn);
+        board.set(5, 4, Piece::BlackKnight);
+        board.set(5, 5, Piece::BlackKnight);
+
+        assert_eq!(
+            valid_moves(&board, &(6, 4)),
+            Bitset::new(0x0000200000000000)
+        );
+    }
+
+    #[test]
+    fn perft_starting_position() {
+        let mut board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+
+        assert_eq!(perft(&mut board, 1), 20);
+        assert_eq!(perft(&mut board, 2), 400);
+        assert_eq!(perft(&mut board, 3), 8902);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        assert_eq!(perft(&mut board, 1), 48);
+        assert_eq!(perft(&mut board, 2), 2039);
+    }
+
+    #[test]
+    fn perft_chess960() {
+        for (fen, nodes) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            for (depth, nodes) in nodes.into_iter().enumerate() {
+                assert_eq!(perft(&mut board, depth as u32 + 1), nodes, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn crazyhouse_drops() {
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[Pn] w KQkq - 0 1")
+                .unwrap();
+
+        // Pawns can't be dropped on the last ranks, which are full anyway.
+        let drops: Vec<_> = legal_moves(&mut board)
+            .into_iter()
+            .filter(|chess_move| chess_move.move_type == MoveType::Drop)
+            .collect();
+        assert_eq!(drops.len(), 32);
+        assert!(drops
+            .iter()
+            .all(|chess_move| chess_move.piece == Piece::WhitePawn));
+
+        let drop = parse_uci_move(&mut board, "P@e4").unwrap();
+        assert_eq!(drop.to_uci(), "P@e4");
+        assert_eq!(to_san(&mut board, &drop), "P@e4");
+        assert_eq!(parse_san(&mut board, "@e4"), Some(drop));
+        for malformed in ["P@e44", "PP@e4", "P@", "@e4"] {
+            assert_eq!(parse_uci_move(&mut board, malformed), None, "{}", malformed);
+        }
+        for malformed in ["P@e44", "NP@e4", "@e", "N@"] {
+            assert_eq!(parse_san(&mut board, malformed), None, "{}", malformed);
+        }
+        assert!(is_pseudo_legal(&board, &drop));
+
+        board.make_move(&drop);
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPPPPPP/RNBQKBNR[n] b KQkq - 0 1"
+        );
+        assert!(!is_pseudo_legal(&board, &drop));
+        board.unmake_move(&drop);
+
+        // Drops can block a check, and count when looking for mate.
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3[N] w - - 0 1").unwrap();
+        assert!(legal_moves(&mut board)
+            .iter()
+            .any(|chess_move| chess_move.to_uci() == "N@d1"));
+        assert_eq!(game_status(&mut board), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn perft_atomic() {
+        for (fen, nodes) in [
+            (STARTING_FEN_STRING, [20, 400, 8902]),
+            (
+                "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq - 0 1",
+                [40, 1238, 45237],
+            ),
+            ("8/8/8/8/8/8/2k5/rR4KR w KQ - 0 1", [18, 180, 4364]),
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            board.variant = &Atomic;
+            for (depth, nodes) in nodes.into_iter().enumerate() {
+                assert_eq!(perft(&mut board, depth as u32 + 1), nodes, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn atomic_explosions() {
+        let fen = "4k3/8/2b5/3np3/4P3/8/8/4K3 w - - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        board.variant = &Atomic;
+        let hash = board.hash;
+
+        // The knight, the bishop and the capturing pawn go; the pawn stays.
+        let capture = parse_uci_move(&mut board, "e4d5").unwrap();
+        board.make_move(&capture);
+        assert_eq!(board.to_fen(), "4k3/8/8/4p3/8/8/8/4K3 b - - 0 1");
+        board.unmake_move(&capture);
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(board.hash, hash);
+
+        // Kings can't capture, touching kings aren't in check, and exploding
+        // the enemy king wins on the spot.
+        let mut board = Board::from_fen("8/8/8/8/8/8/3qk3/3K3R w - - 0 1").unwrap();
+        board.variant = &Atomic;
+        assert!(!in_check(&board, Color::White));
+        assert!(parse_uci_move(&mut board, "d1d2").is_none());
+
+        let mut board = Board::from_fen("7k/6p1/8/8/8/8/8/K5R1 w - - 0 1").unwrap();
+        board.variant = &Atomic;
+        let capture = parse_uci_move(&mut board, "g1g7").unwrap();
+        board.make_move(&capture);
+        assert_eq!(game_status(&mut board), GameStatus::Checkmate(Color::White));
+    }
+
+    #[test]
+    fn perft_racing_kings() {
+        let mut board = Board::from_fen(RacingKings.start_fen()).unwrap();
+        board.variant = &RacingKings;
+
+        assert_eq!(perft(&mut board, 1), 21);
+        assert_eq!(perft(&mut board, 2), 421);
+        assert_eq!(perft(&mut board, 3), 11264);
+    }
+
+    #[test]
+    fn variant_wins() {
+        let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5P2/PPPP2PP/RNBQKBNR b KQkq - 0 2 +1+2";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.variant.name(), "3check");
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(game_status(&mut board), GameStatus::Ongoing);
+
+        let check = parse_uci_move(&mut board, "d8h4").unwrap();
+        board.make_move(&check);
+        assert_eq!(board.checks, [1, 3]);
+        assert_eq!(
+            game_status(&mut board),
+            GameStatus::VariantWin(Color::Black)
+        );
+        board.unmake_move(&check);
+        assert_eq!(board.hash, Board::from_fen(fen).unwrap().hash);
+
+        let mut board = Board::from_fen("8/8/8/3k4/8/8/8/4K3 w - - 0 1").unwrap();
+        board.variant = &KingOfTheHill;
+        assert_eq!(
+            game_status(&mut board),
+            GameStatus::VariantWin(Color::Black)
+        );
+
+        // Racing kings: no checks, and Black gets one move to draw.
+        let mut board = Board::from_fen("4K3/1k6/8/8/8/8/8/7R b - - 0 1").unwrap();
+        board.variant = &RacingKings;
+        assert_eq!(game_status(&mut board), GameStatus::Ongoing);
+        let mut draw = board.clone();
+        let home = parse_uci_move(&mut draw, "b7b8").unwrap();
+        draw.make_move(&home);
+        assert_eq!(game_status(&mut draw), GameStatus::VariantDraw);
+        let sideways = parse_uci_move(&mut board, "b7a6").unwrap();
+        board.make_move(&sideways);
+        assert_eq!(
+            game_status(&mut board),
+            GameStatus::VariantWin(Color::White)
+        );
+
+        let mut board = Board::from_fen("8/8/8/8/8/1k6/8/K6R w - - 0 1").unwrap();
+        board.variant = &RacingKings;
+        assert!(parse_uci_move(&mut board, "h1h3").is_none());
+    }
+
+    #[test]
+    fn antichess_rules() {
+        let mut board = Board::from_fen(Antichess.start_fen()).unwrap();
+        board.variant = &Antichess;
+        assert_eq!(perft(&mut board, 3), 8067);
+
+        // Captures are forced, with the king as an ordinary piece.
+        let mut board = Board::from_fen("8/8/8/8/3k4/4P3/8/K7 b - - 0 1").unwrap();
+        board.variant = &Antichess;
+        let moves = legal_moves(&mut board);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].to_uci(), "d4e3");
+        let quiet = Move {
+            from: (4, 3),
+            to: (4, 2),
+            piece: Piece::BlackKing,
+            move_type: MoveType::Normal,
+        };
+        assert!(!is_pseudo_legal(&board, &quiet));
+
+        // Pawns may promote to a king.
+        let mut board = Board::from_fen("8/4P3/8/8/8/8/8/k7 w - - 0 1").unwrap();
+        board.variant = &Antichess;
+        let promotion = parse_san(&mut board, "e8=K").unwrap();
+        assert_eq!(promotion.promotion(), Some(Piece::WhiteKing));
+
+        // Losing every piece or having no moves wins.
+        let mut board = Board::from_fen("8/8/8/8/8/8/8/k7 w - - 0 1").unwrap();
+        board.variant = &Antichess;
+        assert_eq!(
+            game_status(&mut board),
+            GameStatus::VariantWin(Color::White)
+        );
+
+        let mut board = Board::from_fen("8/8/8/8/8/p7/P7/8 w - - 0 1").unwrap();
+        board.variant = &Antichess;
+        assert_eq!(
+            game_status(&mut board),
+            GameStatus::VariantWin(Color::White)
+        );
+    }
+
+    #[test]
+    fn horde_rules() {
+        let mut board = Board::from_fen(Horde.start_fen()).unwrap();
+        board.variant = &Horde;
+        assert_eq!(perft(&mut board, 3), 1274);
+
+        // First-rank pawns push two squares, without an en passant square.
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/P7 w - - 0 1").unwrap();
+        board.variant = &Horde;
+        let push = parse_uci_move(&mut board, "a1a3").unwrap();
+        board.make_move(&push);
+        assert_eq!(board.en_passant, None);
+        assert_eq!(game_status(&mut board), GameStatus::Ongoing);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/8 w - - 0 1").unwrap();
+        board.variant = &Horde;
+        assert_eq!(
+            game_status(&mut board),
+            GameStatus::VariantWin(Color::Black)
+        );
+    }
+
+    #[test]
+    fn chess960_castling_notation() {
+        // The king on b1 castles queen side by moving one square right.
+        let mut board = Board::from_fen("3k4/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        let castling = parse_uci_move(&mut board, "b1a1").unwrap();
+        assert_eq!(castling.move_type, MoveType::Castling);
+        assert_eq!(castling.to, (7, 2));
+        // Moving the king to its castling square is then an ordinary move.
+        let normal = parse_uci_move(&mut board, "b1c1").unwrap();
+        assert_eq!(normal.move_type, MoveType::Normal);
+        assert_eq!(format_uci(&board, &castling, true), "b1a1");
+        assert_eq!(format_uci(&board, &castling, false), "b1c1");
+
+        board.make_move(&castling);
+        assert_eq!(board.to_fen(), "3k4/8/8/8/8/8/8/2KR3R b - - 1 1");
+        board.unmake_move(&castling);
+        assert_eq!(board.to_fen(), "3k4/8/8/8/8/8/8/RK5R w KQ - 0 1");
+
+        let king_side = parse_uci_move(&mut board, "b1h1").unwrap();
+        assert_eq!(king_side.to, (7, 6));
+    }
+
+    #[test]
+    fn perft_en_passant_and_promotion() {
+        let mut board = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(&mut board, 3), 2812);
+
+        let mut board = Board::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap();
+        assert_eq!(perft(&mut board, 2), 496);
+    }
+
+    #[test]
+    fn make_unmake_restores_board() {
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let fen = board.to_fen();
+        let hash = board.hash;
+
+        for chess_move in legal_moves(&mut board) {
+            board.make_move(&chess_move);
+            board.unmake_move(&chess_move);
+
+            assert_eq!(board.to_fen(), fen);
+            assert_eq!(board.hash, hash);
+        }
+    }
+
+    #[test]
+    fn game_status_checkmate_and_stalemate() {
+        let mut board = Board::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+        assert_eq!(game_status(&mut board), GameStatus::Checkmate(Color::White));
+
+        let mut board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game_status(&mut board), GameStatus::Stalemate);
+    }
+
+    #[test]
+    fn noisy_and_quiet_moves_partition_all_moves() {
+        let board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        let noisy = generate_moves(&board, GenType::Noisy);
+        let quiet = generate_moves(&board, GenType::Quiet);
+
+        assert!(noisy.iter().all(|m| m.is_noisy()));
+        assert!(quiet.iter().all(|m| !m.is_noisy()));
+        assert_eq!(noisy.len() + quiet.len(), pseudo_legal_moves(&board).len());
+    }
+
+    #[test]
+    fn least_valuable_attacker_order() {
+        let board = Board::from_fen("4k3/8/8/3p4/4P3/2N5/8/3QK3 b - - 0 1").unwrap();
+
+        assert_eq!(
+            least_valuable_attacker(&board, (3, 3), Color::White),
+            Some((4, 4))
+        );
+        assert_eq!(
+            least_valuable_attacker(&board, (4, 4), Color::Black),
+            Some((3, 3))
+        );
+        assert_eq!(
+            least_valuable_attacker(&board, (6, 3), Color::White),
+            Some((7, 3))
+        );
+    }
+
     #[test]
     fn queen_empty_board() {
         let mut board = Board::new();
@@ -341,4 +1461,84 @@ mod tests {
             Bitset::new(0x925438ef38549211)
         );
     }
+
+    #[test]
+    fn san_parsing() {
+        let mut board = Board::from_fen("r3k2r/1P6/8/3p4/4P3/1N3N2/8/R3K2R w KQkq - 0 1").unwrap();
+        let san = |board: &mut Board, san: &str| parse_san(board, san).map(|m| m.to_uci());
+
+        assert_eq!(san(&mut board, "exd5"), Some("e4d5".into()));
+        assert_eq!(san(&mut board, "Nbd4"), Some("b3d4".into()));
+        assert_eq!(san(&mut board, "Nfd4"), Some("f3d4".into()));
+        assert_eq!(san(&mut board, "Nd4"), None);
+        assert_eq!(san(&mut board, "O-O+"), Some("e1g1".into()));
+        assert_eq!(san(&mut board, "O-O-O"), Some("e1c1".into()));
+        assert_eq!(san(&mut board, "bxa8=Q#"), Some("b7a8q".into()));
+        assert_eq!(san(&mut board, "b8N"), Some("b7b8n".into()));
+        assert_eq!(san(&mut board, "Ke2!?"), Some("e1e2".into()));
+        assert_eq!(san(&mut board, "Qd1"), None);
+
+        // Writing every legal move reads back as the same move.
+        for fen in [
+            "r3k2r/1P6/8/3p4/4P3/1N3N2/8/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/R3K2R w - - 0 1",
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            for chess_move in legal_moves(&mut board) {
+                let written = to_san(&mut board, &chess_move);
+                assert_eq!(
+                    parse_san(&mut board, &written),
+                    Some(chess_move),
+                    "{}",
+                    written
+                );
+            }
+        }
+
+        let round_trip = |fen: &str, san: &str| {
+            let mut board = Board::from_fen(fen).unwrap();
+            let chess_move = parse_san(&mut board, san).unwrap();
+            to_san(&mut board, &chess_move)
+        };
+        let fen = "r3k2r/1P6/8/3p4/4P3/1N3N2/8/R3K2R w KQkq - 0 1";
+        assert_eq!(round_trip(fen, "Nbd4"), "Nbd4");
+        assert_eq!(round_trip(fen, "bxa8Q"), "bxa8=Q+");
+        assert_eq!(round_trip(fen, "O-O-O"), "O-O-O");
+        assert_eq!(
+            round_trip("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1", "Ra8"),
+            "Ra8#"
+        );
+    }
+
+    #[test]
+    fn unmoves_retract_legal_moves() {
+        for fen in [
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/8/8/8/3Qb3/8/1N6/4K3 b - - 0 1",
+            "8/8/4k3/8/2n5/8/4K3/8 w - - 0 1",
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+
+            // Every quiet move played here is retracted from the result.
+            for chess_move in legal_moves(&mut board) {
+                if chess_move.is_noisy() || chess_move.move_type != MoveType::Normal {
+                    continue;
+                }
+
+                board.make_move(&chess_move);
+                assert!(unmoves(&board).contains(&chess_move), "{}", chess_move);
+                board.unmake_move(&chess_move);
+            }
+
+            // And every retraction leads back with a legal move.
+            for unmove in unmoves(&board) {
+                let mut previous = retract(&board, &unmove);
+                assert!(legal_moves(&mut previous).contains(&unmove), "{}", unmove);
+
+                previous.make_move(&unmove);
+                assert_eq!(previous.pieces, board.pieces);
+            }
+        }
+    }
 }
@@ -0,0 +1,440 @@
+use crate::{
+    board::Board,
+    movegen::{
+        generate_moves, is_attacked, is_pseudo_legal, least_valuable_attacker, GenType, Move,
+        MoveType,
+    },
+};
+
+const SEE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20000];
+const MAX_HISTORY: i32 = 16384;
+
+fn see_value(chess_move: &Move) -> i32 {
+    chess_move
+        .captured()
+        .map(|piece| SEE_VALUES[piece.kind()])
+        .unwrap_or(0)
+}
+
+// Static exchange evaluation: the material balance of playing out all
+// captures on the target square, cheapest attacker first.
+pub fn see(board: &mut Board, chess_move: &Move) -> i32 {
+    if !board.variant.has_exchanges() {
+        return see_value(chess_move);
+    }
+
+    let to = chess_move.to;
+
+    // A promotion gains the new piece in place of the pawn, so a queen
+    // promotion only loses material if the queen is won for less than that.
+    let promotion_gain = chess_move
+        .promotion()
+        .map_or(0, |piece| SEE_VALUES[piece.kind()] - SEE_VALUES[0]);
+    let mut gains = vec![see_value(chess_move) + promotion_gain];
+    let mut on_square = SEE_VALUES[chess_move.promotion().unwrap_or(chess_move.piece).kind()];
+
+    let mut played = vec![*chess_move];
+    board.make_move(chess_move);
+
+    loop {
+        let side = board.side_to_move;
+        let Some(from) = least_valuable_attacker(board, to, side) else {
+            break;
+        };
+
+        let piece = board.get(from.0, from.1).unwrap();
+        if piece.is_king() && is_attacked(board, to, side.opposite()) {
+            break;
+        }
+
+        let gain = on_square - gains[gains.len() - 1];
+        gains.push(gain);
+        if (-gains[gains.len() - 2]).max(gain) < 0 {
+            break;
+        }
+
+        let capture = Move {
+            from,
+            to,
+            piece,
+            move_type: MoveType::Capture(board.get(to.0, to.1).unwrap()),
+        };
+        board.make_move(&capture);
+        played.push(capture);
+        on_square = SEE_VALUES[piece.kind()];
+    }
+
+    for chess_move in played.iter().rev() {
+        board.unmake_move(chess_move);
+    }
+
+    for depth in (1..gains.len()).rev() {
+        gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+    }
+
+    gains[0]
+}
+
+fn mvv_lva(chess_move: &Move) -> i32 {
+    let promotion = chess_move
+        .promotion()
+        .map(|piece| SEE_VALUES[piece.kind()])
+        .unwrap_or(0);
+
+    (see_value(chess_move) + promotion) * 8 - chess_move.piece.kind() as i32
+}
+
+pub struct Heuristics {
+    killers: Vec<[Option<Move>; 2]>,
+    counters: Vec<Option<Move>>,
+    history: Vec<i32>,
+}
+
+impl Heuristics {
+    pub fn new(max_ply: usize) -> Self {
+        Self {
+            killers: vec![[None; 2]; max_ply + 1],
+            counters: vec![None; 12 * 64],
+            history: vec![0; 2 * 64 * 64],
+        }
+    }
+
+    fn counter_index(previous: &Move) -> usize {
+        previous.piece.index() * 64 + (previous.to.0 * 8 + previous.to.1) as usize
+    }
+
+    fn history_index(chess_move: &Move) -> usize {
+        let color = chess_move.piece.color().index() - 1;
+        let from = (chess_move.from.0 * 8 + chess_move.from.1) as usize;
+        let to = (chess_move.to.0 * 8 + chess_move.to.1) as usize;
+
+        color * 4096 + from * 64 + to
+    }
+
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers[ply]
+    }
+
+    pub fn counter(&self, previous: Option<Move>) -> Option<Move> {
+        previous.and_then(|previous| self.counters[Self::counter_index(&previous)])
+    }
+
+    pub fn history(&self, chess_move: &Move) -> i32 {
+        self.history[Self::history_index(chess_move)]
+    }
+
+    fn add_history(&mut self, chess_move: &Move, bonus: i32) {
+        let entry = &mut self.history[Self::history_index(chess_move)];
+        *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+    }
+
+    // Rewards a quiet move that caused a beta cutoff and penalises the quiet
+    // moves searched before it.
+    pub fn update_quiet(
+        &mut self,
+        ply: usize,
+        previous: Option<Move>,
+        best_move: &Move,
+        tried: &[Move],
+        depth: u32,
+    ) {
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(*best_move) {
+            killers[1] = killers[0];
+            killers[0] = Some(*best_move);
+        }
+
+        if let Some(previous) = previous {
+            self.counters[Self::counter_index(&previous)] = Some(*best_move);
+        }
+
+        let bonus = (depth * depth) as i32;
+        self.add_history(best_move, bonus.min(MAX_HISTORY));
+        for chess_move in tried.iter().filter(|m| *m != best_move) {
+            self.add_history(chess_move, -bonus.min(MAX_HISTORY));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.killers.fill([None; 2]);
+        self.counters.fill(None);
+        self.history.fill(0);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateNoisy,
+    GoodNoisy,
+    Refutations,
+    GenerateQuiet,
+    Quiet,
+    BadNoisy,
+    GenerateUnordered,
+    Unordered,
+    Done,
+}
+
+// Yields moves one at a time in stages, so that a cutoff on the hash move or a
+// good capture saves generating and scoring the rest.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    refutations: Vec<Move>,
+    played_refutations: Vec<Move>,
+    moves: Vec<(Move, i32)>,
+    bad_noisy: Vec<Move>,
+    index: usize,
+    noisy_only: bool,
+}
+
+impl MovePicker {
+    pub fn new(
+        hash_move: Option<Move>,
+        killers: [Option<Move>; 2],
+        counter: Option<Move>,
+        ordered: bool,
+    ) -> Self {
+        let mut refutations = Vec::new();
+        for chess_move in [killers[0], killers[1], counter].into_iter().flatten() {
+            if Some(chess_move) != hash_move && !refutations.contains(&chess_move) {
+                refutations.push(chess_move);
+            }
+        }
+
+        Self {
+            stage: if ordered {
+                Stage::HashMove
+            } else {
+                Stage::GenerateUnordered
+            },
+            hash_move,
+            refutations,
+            played_refutations: Vec::new(),
+            moves: Vec::new(),
+            bad_noisy: Vec::new(),
+            index: 0,
+            noisy_only: false,
+        }
+    }
+
+    // Captures and promotions only, skipping those that lose material even
+    // when ordering is disabled.
+    pub fn new_noisy(ordered: bool) -> Self {
+        let mut picker = Self::new(None, [None; 2], None, ordered);
+        picker.noisy_only = true;
+        picker
+    }
+
+    fn select_best(&mut self) -> Option<Move> {
+        if self.index >= self.moves.len() {
+            return None;
+        }
+
+        let best = (self.index..self.moves.len())
+            .max_by_key(|index| self.moves[*index].1)
+            .unwrap();
+        self.moves.swap(self.index, best);
+        self.index += 1;
+
+        Some(self.moves[self.index - 1].0)
+    }
+
+    pub fn next(&mut self, board: &mut Board, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateNoisy;
+
+                    if let Some(hash_move) = self.hash_move {
+                        if (!self.noisy_only || hash_move.is_noisy())
+                            && is_pseudo_legal(board, &hash_move)
+                        {
+                            return Some(hash_move);
+                        }
+                    }
+                }
+
+                Stage::GenerateNoisy => {
+                    self.moves = generate_moves(board, GenType::Noisy)
+                        .into_iter()
+                        .filter(|m| self.hash_move != Some(*m))
+                        .map(|m| (m, mvv_lva(&m)))
+                        .collect();
+                    self.index = 0;
+                    self.stage = Stage::GoodNoisy;
+                }
+
+                Stage::GoodNoisy => match self.select_best() {
+                    Some(chess_move) => {
+                        let attacker = SEE_VALUES[chess_move.piece.kind()];
+                        if attacker > see_value(&chess_move) && see(board, &chess_move) < 0 {
+                            self.bad_noisy.push(chess_move);
+                            continue;
+                        }
+
+                        return Some(chess_move);
+                    }
+                    None => {
+                        self.stage = if self.noisy_only {
+                            Stage::Done
+                        } else {
+                            Stage::Refutations
+                        };
+                    }
+                },
+
+                Stage::Refutations => {
+                    if self.refutations.is_empty() {
+                        self.stage = Stage::GenerateQuiet;
+                        continue;
+                    }
+
+                    let chess_move = self.refutations.remove(0);
+                    if !chess_move.is_noisy() && is_pseudo_legal(board, &chess_move) {
+                        self.played_refutations.push(chess_move);
+                        return Some(chess_move);
+                    }
+                }
+
+                Stage::GenerateQuiet => {
+                    self.moves = generate_moves(board, GenType::Quiet)
+                        .into_iter()
+                        .filter(|m| {
+                            self.hash_move != Some(*m) && !self.played_refutations.contains(m)
+                        })
+                        .map(|m| (m, heuristics.history(&m)))
+                        .collect();
+                    self.index = 0;
+                    self.stage = Stage::Quiet;
+                }
+
+                Stage::Quiet => match self.select_best() {
+                    Some(chess_move) => return Some(chess_move),
+                    None => {
+                        self.index = 0;
+                        self.stage = Stage::BadNoisy;
+                    }
+                },
+
+                Stage::BadNoisy => {
+                    if self.index < self.bad_noisy.len() {
+                        self.index += 1;
+                        return Some(self.bad_noisy[self.index - 1]);
+                    }
+                    self.stage = Stage::Done;
+                }
+
+                Stage::GenerateUnordered => {
+                    let gen_type = if self.noisy_only {
+                        GenType::Noisy
+                    } else {
+                        GenType::All
+                    };
+                    self.moves = generate_moves(board, gen_type)
+                        .into_iter()
+                        .map(|m| (m, 0))
+                        .collect();
+                    self.index = 0;
+                    self.stage = Stage::Unordered;
+                }
+
+                Stage::Unordered => {
+                    if self.index < self.moves.len() {
+                        self.index += 1;
+
+                        let chess_move = self.moves[self.index - 1].0;
+                        if self.noisy_only && see(board, &chess_move) < 0 {
+                            continue;
+                        }
+                        return Some(chess_move);
+                    }
+                    self.stage = Stage::Done;
+                }
+
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::{parse_uci_move, pseudo_legal_moves};
+
+    fn picked(board: &mut Board, picker: &mut MovePicker) -> Vec<Move> {
+        let heuristics = Heuristics::new(8);
+        let mut moves = Vec::new();
+        while let Some(chess_move) = picker.next(board, &heuristics) {
+            moves.push(chess_move);
+        }
+        moves
+    }
+
+    #[test]
+    fn see_exchanges() {
+        let mut board = Board::from_fen("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        let capture = parse_uci_move(&mut board, "e4d5").unwrap();
+        assert_eq!(see(&mut board, &capture), 220);
+
+        let mut board = Board::from_fen("4k3/8/2p5/3p4/8/8/3Q4/3RK3 w - - 0 1").unwrap();
+        let capture = parse_uci_move(&mut board, "d2d5").unwrap();
+        assert_eq!(see(&mut board, &capture), -700);
+
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1").unwrap();
+        let capture = parse_uci_move(&mut board, "d2d5").unwrap();
+        assert_eq!(see(&mut board, &capture), 100);
+    }
+
+    #[test]
+    fn promotions_count_the_new_piece() {
+        let mut board = Board::from_fen("r5k1/4P3/8/8/8/8/8/4R1K1 w - - 0 1").unwrap();
+        let promotion = parse_uci_move(&mut board, "e7e8q").unwrap();
+        assert_eq!(see(&mut board, &promotion), 400);
+
+        let mut picker = MovePicker::new(None, [None; 2], None, true);
+        assert_eq!(picked(&mut board, &mut picker)[0], promotion);
+
+        let mut board = Board::from_fen("r5k1/4P3/8/8/8/8/8/6K1 w - - 0 1").unwrap();
+        let promotion = parse_uci_move(&mut board, "e7e8q").unwrap();
+        assert_eq!(see(&mut board, &promotion), -100);
+    }
+
+    #[test]
+    fn picker_yields_every_move_once() {
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let hash_move = parse_uci_move(&mut board, "e2a6");
+        let killer = parse_uci_move(&mut board, "a2a3");
+
+        let mut picker = MovePicker::new(hash_move, [killer, None], None, true);
+        let moves = picked(&mut board, &mut picker);
+
+        let mut expected = pseudo_legal_moves(&board);
+        assert_eq!(moves.len(), expected.len());
+        for chess_move in &moves {
+            let index = expected.iter().position(|m| m == chess_move).unwrap();
+            expected.remove(index);
+        }
+
+        assert_eq!(moves[0], hash_move.unwrap());
+    }
+
+    #[test]
+    fn captures_ordered_by_victim_and_losing_captures_last() {
+        let mut board = Board::from_fen("4k3/8/2p5/1q1p4/2P5/8/3Q4/4K3 w - - 0 1").unwrap();
+
+        let mut picker = MovePicker::new(None, [None; 2], None, true);
+        let moves: Vec<_> = picked(&mut board, &mut picker)
+            .iter()
+            .map(|m| m.to_uci())
+            .collect();
+
+        assert_eq!(moves[0], "c4b5");
+        assert_eq!(moves[1], "c4d5");
+        assert_eq!(moves[moves.len() - 1], "d2d5");
+    }
+}
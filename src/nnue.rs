@@ -0,0 +1,322 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    board::Board,
+    piece::{Color, Piece},
+};
+
+// Quantisation of the feature layer, the output layer and the final score,
+// as used by the usual trainers for this architecture.
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+// One input per piece and square, seen from each side: the side's own
+// pieces first and the board flipped for Black.
+const FEATURES: usize = 768;
+
+fn feature(perspective: Color, piece: Piece, square: u8) -> usize {
+    let (relative, square) = match perspective {
+        Color::White => (piece.color() != Color::White, square ^ 56),
+        Color::Black => (piece.color() != Color::Black, square),
+    };
+
+    (relative as usize * 6 + piece.kind()) * 64 + square as usize
+}
+
+// A (768 -> N) x 2 -> 1 network with clipped ReLU activation. The file
+// holds little-endian i16 values: feature weights, feature biases, output
+// weights for the side to move and then the other side, and the output
+// bias, optionally padded to a multiple of 64 bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let values: Vec<_> = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+
+        let hidden = values.len().saturating_sub(1) / (FEATURES + 3);
+        let used = hidden * (FEATURES + 3) + 1;
+        if hidden == 0 || bytes.len() - 2 * used >= 64 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "network size doesn't match a (768 -> N) x 2 -> 1 network",
+            ));
+        }
+
+        let (feature_weights, rest) = values.split_at(FEATURES * hidden);
+        let (feature_bias, rest) = rest.split_at(hidden);
+        let (output_weights, rest) = rest.split_at(2 * hidden);
+
+        Ok(Self {
+            hidden,
+            feature_weights: feature_weights.to_vec(),
+            feature_bias: feature_bias.to_vec(),
+            output_weights: output_weights.to_vec(),
+            output_bias: rest[0],
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.feature_weights
+            .iter()
+            .chain(&self.feature_bias)
+            .chain(&self.output_weights)
+            .chain([&self.output_bias])
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn hidden(&self) -> usize {
+        self.hidden
+    }
+
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    fn output(&self, us: &[i16], them: &[i16]) -> i32 {
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let output =
+            crelu_dot(us, our_weights) + crelu_dot(them, their_weights) + self.output_bias as i32;
+
+        output * SCALE / (QA * QB)
+    }
+}
+
+fn crelu_dot(accumulator: &[i16], weights: &[i16]) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        // Safe: AVX2 support was just detected.
+        return unsafe { crelu_dot_avx2(accumulator, weights) };
+    }
+
+    crelu_dot_portable(accumulator, weights)
+}
+
+fn crelu_dot_portable(accumulator: &[i16], weights: &[i16]) -> i32 {
+    accumulator
+        .iter()
+        .zip(weights)
+        .map(|(&value, &weight)| (value as i32).clamp(0, QA) * weight as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn crelu_dot_avx2(accumulator: &[i16], weights: &[i16]) -> i32 {
+    use std::arch::x86_64::*;
+
+    let zero = _mm256_setzero_si256();
+    let qa = _mm256_set1_epi16(QA as i16);
+    let mut sum = _mm256_setzero_si256();
+
+    let chunks = accumulator.len().min(weights.len()) / 16;
+    for chunk in 0..chunks {
+        let values = _mm256_loadu_si256(accumulator.as_ptr().add(chunk * 16) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(chunk * 16) as *const __m256i);
+
+        // Clipped values fit in 8 bits, so pairwise products can't overflow.
+        let clipped = _mm256_min_epi16(_mm256_max_epi16(values, zero), qa);
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(clipped, weight));
+    }
+
+    let mut lanes = [0i32; 8];
+    _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+
+    lanes.iter().sum::<i32>()
+        + crelu_dot_portable(&accumulator[chunks * 16..], &weights[chunks * 16..])
+}
+
+// The feature layer's output for both perspectives, one entry per ply so that
+// unmaking a move only has to step back.
+#[derive(Clone)]
+pub struct Accumulators {
+    network: Arc<Network>,
+    stack: Vec<[Vec<i16>; 2]>,
+    ply: usize,
+}
+
+impl Accumulators {
+    pub fn new(network: Arc<Network>, board: &Board) -> Self {
+        let mut accumulators = Self {
+            stack: vec![[network.feature_bias.clone(), network.feature_bias.clone()]],
+            network,
+            ply: 0,
+        };
+
+        for (index, bitset) in board.pieces.iter().enumerate() {
+            let piece = Piece::from_index(index).unwrap();
+            for square in bitset.set_bit_indices() {
+                accumulators.add(piece, square);
+            }
+        }
+
+        accumulators
+    }
+
+    // Starts a new ply from a copy of the current one, reusing old buffers.
+    pub fn push(&mut self) {
+        self.ply += 1;
+        if self.ply == self.stack.len() {
+            self.stack.push(self.stack[self.ply - 1].clone());
+        } else {
+            let (previous, current) = self.stack.split_at_mut(self.ply);
+            for perspective in 0..2 {
+                current[0][perspective].copy_from_slice(&previous[self.ply - 1][perspective]);
+            }
+        }
+    }
+
+    pub fn pop(&mut self) {
+        self.ply -= 1;
+    }
+
+    fn update(&mut self, piece: Piece, square: u8, sign: i16) {
+        for (perspective, color) in [Color::White, Color::Black].into_iter().enumerate() {
+            let weights = self.network.weights(feature(color, piece, square));
+            let values = &mut self.stack[self.ply][perspective];
+
+            for (value, &weight) in values.iter_mut().zip(weights) {
+                *value = value.wrapping_add(sign.wrapping_mul(weight));
+            }
+        }
+    }
+
+    pub fn add(&mut self, piece: Piece, square: u8) {
+        self.update(piece, square, 1);
+    }
+
+    pub fn remove(&mut self, piece: Piece, square: u8) {
+        self.update(piece, square, -1);
+    }
+
+    // Score from the side to move's point of view.
+    pub fn evaluate(&self, side_to_move: Color) -> i32 {
+        let [white, black] = &self.stack[self.ply];
+        match side_to_move {
+            Color::White => self.network.output(white, black),
+            Color::Black => self.network.output(black, white),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+
+    // A small network with weights from a fixed pseudo-random sequence.
+    pub fn test_network(hidden: usize) -> Network {
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let mut next = |range: i64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            ((state % (2 * range as u64 + 1)) as i64 - range) as i16
+        };
+
+        Network {
+            hidden,
+            feature_weights: (0..FEATURES * hidden).map(|_| next(60)).collect(),
+            feature_bias: (0..hidden).map(|_| next(60)).collect(),
+            output_weights: (0..2 * hidden).map(|_| next(120)).collect(),
+            output_bias: next(1000),
+        }
+    }
+
+    #[test]
+    fn network_file_round_trip() {
+        let network = test_network(8);
+        let mut bytes = network.to_bytes();
+        assert_eq!(Network::from_bytes(&bytes).unwrap(), network);
+
+        bytes.extend([0; 30]);
+        assert_eq!(Network::from_bytes(&bytes).unwrap(), network);
+
+        assert!(Network::from_bytes(&bytes[..100]).is_err());
+    }
+
+    #[test]
+    fn simd_matches_portable_dot_product() {
+        let network = test_network(37);
+        let accumulator: Vec<_> = network
+            .feature_weights
+            .iter()
+            .take(37)
+            .map(|weight| weight * 7)
+            .collect();
+
+        assert_eq!(
+            crelu_dot(&accumulator, &network.output_weights[..37]),
+            crelu_dot_portable(&accumulator, &network.output_weights[..37])
+        );
+    }
+
+    #[test]
+    fn incremental_updates_match_refresh() {
+        let network = Arc::new(test_network(32));
+        let refreshed = |board: &Board| {
+            let accumulators = Accumulators::new(network.clone(), board);
+            accumulators.stack[0].clone()
+        };
+        let current = |board: &Board| {
+            let accumulators = board.accumulators().unwrap();
+            accumulators.stack[accumulators.ply].clone()
+        };
+
+        // Castling, captures, promotions and en passant.
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 1",
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            board.set_network(Some(network.clone()));
+
+            for chess_move in legal_moves(&mut board) {
+                board.make_move(&chess_move);
+                assert_eq!(current(&board), refreshed(&board), "{}", chess_move);
+
+                for reply in legal_moves(&mut board).into_iter().take(3) {
+                    board.make_move(&reply);
+                    assert_eq!(current(&board), refreshed(&board), "{}", reply);
+                    board.unmake_move(&reply);
+                }
+
+                board.unmake_move(&chess_move);
+                assert_eq!(current(&board), refreshed(&board));
+            }
+        }
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        let network = Arc::new(test_network(16));
+        let mut white = Board::from_fen("4k3/8/8/8/8/8/3P4/4K1N1 w - - 0 1").unwrap();
+        let mut black = Board::from_fen("4k1n1/3p4/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        white.set_network(Some(network.clone()));
+        black.set_network(Some(network));
+
+        let score = |board: &Board| board.accumulators().unwrap().evaluate(board.side_to_move);
+        assert_eq!(score(&white), score(&black));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::board::{Board, STARTING_FEN_STRING};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl GameResult {
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token {
+            "1-0" => Some(Self::WhiteWins),
+            "0-1" => Some(Self::BlackWins),
+            "1/2-1/2" => Some(Self::Draw),
+            "*" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn token(self) -> &'static str {
+        match self {
+            Self::WhiteWins => "1-0",
+            Self::BlackWins => "0-1",
+            Self::Draw => "1/2-1/2",
+            Self::Unknown => "*",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn result(&self) -> GameResult {
+        self.tag("Result")
+            .and_then(GameResult::from_token)
+            .unwrap_or(GameResult::Unknown)
+    }
+
+    pub fn start_position(&self) -> Option<Board> {
+        Board::from_fen(self.tag("FEN").unwrap_or(STARTING_FEN_STRING))
+    }
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let line = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = line.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    Some((name.to_string(), value.replace("\\\"", "\"")))
+}
+
+// Adds a finished movetext word to the game. Returns whether it was the
+// result token that ends the game.
+fn flush(token: &mut String, game: &mut PgnGame) -> bool {
+    let word = std::mem::take(token);
+    if word.is_empty() {
+        return false;
+    }
+
+    if GameResult::from_token(&word).is_some() {
+        if game.tag("Result").is_none() {
+            game.tags.push(("Result".to_string(), word));
+        }
+        return true;
+    }
+
+    // Move numbers, possibly glued to the move as in "1.e4" or "3...Nf6".
+    // Digits without a dot are part of the move, as in "0-0".
+    let word = match word
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .strip_prefix('.')
+    {
+        Some(rest) => rest.trim_start_matches('.'),
+        None => &word,
+    };
+    if !word.is_empty() && !word.starts_with('$') {
+        game.moves.push(word.to_string());
+    }
+    false
+}
+
+// Splits a line of movetext into moves, dropping move numbers, comments,
+// variations and annotation glyphs.
+fn push_movetext(game: &mut PgnGame, text: &str, depth: &mut usize, in_comment: &mut bool) -> bool {
+    let mut token = String::new();
+    let mut finished = false;
+
+    for c in text.chars() {
+        if *in_comment {
+            *in_comment = c != '}';
+            continue;
+        }
+
+        match c {
+            '{' => {
+                finished |= flush(&mut token, game);
+                *in_comment = true;
+            }
+            ';' => {
+                finished |= flush(&mut token, game);
+                break;
+            }
+            '(' => {
+                finished |= flush(&mut token, game);
+                *depth += 1;
+            }
+            ')' => {
+                token.clear();
+                *depth = depth.saturating_sub(1);
+            }
+            _ if *depth > 0 => {}
+            c if c.is_whitespace() => finished |= flush(&mut token, game),
+            c => token.push(c),
+        }
+    }
+
+    if *depth == 0 {
+        finished |= flush(&mut token, game);
+    }
+    finished
+}
+
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::default();
+    let mut depth = 0;
+    let mut in_comment = false;
+
+    for line in text.lines() {
+        if !in_comment && depth == 0 {
+            if line.starts_with('%') {
+                continue;
+            }
+
+            if line.trim_start().starts_with('[') {
+                // A tag after movetext starts the next game even if the
+                // previous one had no result token.
+                if !game.moves.is_empty() {
+                    games.push(std::mem::take(&mut game));
+                }
+                if let Some(tag) = parse_tag(line) {
+                    game.tags.push(tag);
+                }
+                continue;
+            }
+        }
+
+        if push_movetext(&mut game, line, &mut depth, &mut in_comment) {
+            games.push(std::mem::take(&mut game));
+        }
+    }
+
+    if !game.moves.is_empty() {
+        games.push(game);
+    }
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_moves_and_results() {
+        let pgn = r#"[Event "Casual"]
+[White "A"]
+[Result "1-0"]
+
+1. e4 e5 2. Nf3 {develops} Nc6 (2... d6 3. d4) 3. Bb5 $1 a6 ; Ruy Lopez
+4.Ba4 Nf6 1-0
+
+[Event "Second"]
+
+1. d4 d5 1/2-1/2
+"#;
+
+        let games = parse_pgn(pgn);
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].result(), GameResult::WhiteWins);
+        assert_eq!(
+            games[0].moves,
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6"]
+        );
+
+        assert_eq!(games[1].tag("Event"), Some("Second"));
+        assert_eq!(games[1].result(), GameResult::Draw);
+        assert_eq!(games[1].moves, ["d4", "d5"]);
+    }
+
+    #[test]
+    fn multiline_comments_and_nested_variations() {
+        let pgn = "1. e4 {a comment\nspanning (lines} c5 (1... e5 (1... e6) 2. Nf3\n) 2. Nf3 0-1";
+
+        let games = parse_pgn(pgn);
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves, ["e4", "c5", "Nf3"]);
+        assert_eq!(games[0].result(), GameResult::BlackWins);
+    }
+
+    #[test]
+    fn zero_castling_keeps_its_digits() {
+        let games = parse_pgn("12. 0-0 0-0-0 13.Kb1 13...Kb8 14.0-0-0 *");
+        assert_eq!(games[0].moves, ["0-0", "0-0-0", "Kb1", "Kb8", "0-0-0"]);
+    }
+}
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Color {
     White,
     Black,
@@ -96,6 +96,26 @@ impl Piece {
         }
     }
 
+    pub fn kind(&self) -> usize {
+        self.index() % 6
+    }
+
+    pub fn from_kind(kind: usize, color: Color) -> Option<Self> {
+        if kind < 6 {
+            Self::from_index(kind + 6 * (color.index() - 1))
+        } else {
+            None
+        }
+    }
+
+    pub fn is_pawn(&self) -> bool {
+        self.kind() == 0
+    }
+
+    pub fn is_king(&self) -> bool {
+        self.kind() == 5
+    }
+
     pub fn color(&self) -> Color {
         match self {
             Self::WhitePawn => Color::White,
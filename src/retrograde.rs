@@ -0,0 +1,739 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::{
+    board::Board,
+    movegen::{generate_moves, in_check, pseudo_legal_moves, retract, unmoves, GenType, Move},
+    piece::{Color, Piece},
+};
+
+const MAGIC: [u8; 4] = *b"CDTM";
+pub const EXTENSION: &str = "dtm";
+
+// Larger tables would take gigabytes with one byte per position.
+pub const MAX_PIECES: usize = 4;
+
+// Distance to mate in plies from the side to move's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Dtm {
+    // Positions are stored as plies to mate plus one, with 0 for draws and
+    // positions that are illegal or not canonical. Odd plies are wins for
+    // the side to move, even ones losses.
+    fn from_value(value: u8) -> Self {
+        match value {
+            0 => Self::Draw,
+            _ if value.is_multiple_of(2) => Self::Win(value as u32 - 1),
+            _ => Self::Loss(value as u32 - 1),
+        }
+    }
+}
+
+// Material signature such as "KQvK", with white first.
+pub fn material_key(board: &Board) -> String {
+    let side = |color| {
+        [5, 4, 3, 2, 1, 0]
+            .into_iter()
+            .map(|kind| {
+                let piece = Piece::from_kind(kind, color).unwrap();
+                let count = board.pieces[piece.index()].count() as usize;
+                piece
+                    .simple_char()
+                    .to_ascii_uppercase()
+                    .to_string()
+                    .repeat(count)
+            })
+            .collect::<String>()
+    };
+
+    format!("{}v{}", side(Color::White), side(Color::Black))
+}
+
+fn side_key(side: &str) -> (usize, Vec<Option<usize>>) {
+    (side.len(), side.chars().map(|c| "PNBRQK".find(c)).collect())
+}
+
+// Pieces of a table name such as "KRvKP", white first. Each side lists its
+// king and then the other pieces from queen down to pawn, as in
+// `material_key`.
+fn parse_material(name: &str) -> Option<Vec<Piece>> {
+    let (white, black) = name.split_once('v')?;
+    let mut pieces = Vec::new();
+
+    for (side, color) in [(white, Color::White), (black, Color::Black)] {
+        if !side.starts_with('K') || side[1..].contains('K') {
+            return None;
+        }
+
+        let mut kinds = side
+            .chars()
+            .map(|c| Piece::from_char(c).map(|piece| piece.kind()))
+            .collect::<Option<Vec<_>>>()?;
+        kinds.sort_by(|a, b| b.cmp(a));
+        pieces.extend(
+            kinds
+                .into_iter()
+                .map(|kind| Piece::from_kind(kind, color).unwrap()),
+        );
+    }
+
+    (pieces.len() <= MAX_PIECES).then_some(pieces)
+}
+
+fn material_name(pieces: &[Piece]) -> String {
+    let side = |color| {
+        pieces
+            .iter()
+            .filter(|piece| piece.color() == color)
+            .map(|piece| piece.simple_char().to_ascii_uppercase())
+            .collect::<String>()
+    };
+
+    format!("{}v{}", side(Color::White), side(Color::Black))
+}
+
+// The orientation a material is stored under: the stronger side as white.
+pub fn normalize(name: &str) -> Option<String> {
+    let pieces = parse_material(name)?;
+    let name = material_name(&pieces);
+    let (white, black) = name.split_once('v')?;
+
+    if side_key(black) > side_key(white) {
+        Some(format!("{}v{}", black, white))
+    } else {
+        Some(name)
+    }
+}
+
+fn flip_square((rank, file): (u8, u8)) -> (u8, u8) {
+    (7 - rank, file)
+}
+
+// One of the eight symmetries of the board: bit 0 mirrors the files, bit 1
+// the ranks and bit 2 swaps ranks and files.
+fn transform(symmetry: usize, (mut rank, mut file): (u8, u8)) -> (u8, u8) {
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    if symmetry & 4 != 0 {
+        (rank, file) = (file, rank);
+    }
+    (rank, file)
+}
+
+// How positions of one material map to table indices. Symmetric positions
+// share an index: with pawns the white king is kept on files a-d, without
+// them in the a1-d1-d4 triangle. Each index then holds the side to move,
+// the white king and the other pieces in order.
+struct Layout {
+    pieces: Vec<Piece>,
+    pawns: bool,
+    king_squares: Vec<(u8, u8)>,
+}
+
+impl Layout {
+    fn new(pieces: Vec<Piece>) -> Self {
+        let pawns = pieces.iter().any(Piece::is_pawn);
+        let king_squares = (0..8)
+            .flat_map(|rank| (0..4).map(move |file| (rank, file)))
+            .filter(|&(rank, file)| pawns || 7 - rank <= file)
+            .collect();
+
+        Self {
+            pieces,
+            pawns,
+            king_squares,
+        }
+    }
+
+    fn size(&self) -> usize {
+        2 * self.king_squares.len() * 64usize.pow(self.pieces.len() as u32 - 1)
+    }
+
+    fn index(&self, color: Color, squares: &[(u8, u8)]) -> Option<usize> {
+        let king = self
+            .king_squares
+            .iter()
+            .position(|&square| square == squares[0])?;
+        let mut index = (color == Color::Black) as usize * self.king_squares.len() + king;
+
+        for &(rank, file) in &squares[1..] {
+            index = index * 64 + (rank * 8 + file) as usize;
+        }
+        Some(index)
+    }
+
+    fn decode(&self, mut index: usize) -> (Color, Vec<(u8, u8)>) {
+        let mut squares = vec![(0, 0); self.pieces.len()];
+
+        for square in squares[1..].iter_mut().rev() {
+            *square = ((index % 64 / 8) as u8, (index % 8) as u8);
+            index /= 64;
+        }
+        squares[0] = self.king_squares[index % self.king_squares.len()];
+
+        let color = if index < self.king_squares.len() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        (color, squares)
+    }
+
+    // The smallest index among the symmetric images of a position, with
+    // identical pieces in square order.
+    fn canonical_index(&self, color: Color, squares: &[(u8, u8)]) -> Option<usize> {
+        let symmetries = if self.pawns { 0..2 } else { 0..8 };
+
+        symmetries
+            .filter_map(|symmetry| {
+                let mut squares: Vec<_> = squares
+                    .iter()
+                    .map(|&square| transform(symmetry, square))
+                    .collect();
+
+                let mut start = 0;
+                while start < squares.len() {
+                    let end = (start..self.pieces.len())
+                        .find(|&end| self.pieces[end] != self.pieces[start])
+                        .unwrap_or(self.pieces.len());
+                    squares[start..end].sort();
+                    start = end;
+                }
+
+                self.index(color, &squares)
+            })
+            .min()
+    }
+
+    // The squares of the table's pieces on a board, with colours swapped and
+    // the board flipped when the board holds the mirrored material.
+    fn placement(&self, board: &Board, flip: bool) -> (Color, Vec<(u8, u8)>) {
+        let mut squares = Vec::with_capacity(self.pieces.len());
+
+        for (index, piece) in self.pieces.iter().enumerate() {
+            if index > 0 && self.pieces[index - 1] == *piece {
+                continue;
+            }
+
+            let color = if flip {
+                piece.color().opposite()
+            } else {
+                piece.color()
+            };
+            let piece = Piece::from_kind(piece.kind(), color).unwrap();
+
+            for square in board.pieces[piece.index()].set_bit_indices() {
+                let square = (square / 8, square % 8);
+                squares.push(if flip { flip_square(square) } else { square });
+            }
+        }
+
+        let color = if flip {
+            board.side_to_move.opposite()
+        } else {
+            board.side_to_move
+        };
+        (color, squares)
+    }
+
+    // The position at an index, if it's legal and the index is canonical.
+    fn board(&self, index: usize) -> Option<Board> {
+        let (color, squares) = self.decode(index);
+
+        for (i, square) in squares.iter().enumerate() {
+            if squares[..i].contains(square)
+                || (self.pieces[i].is_pawn() && (square.0 == 0 || square.0 == 7))
+            {
+                return None;
+            }
+        }
+
+        if self.canonical_index(color, &squares) != Some(index) {
+            return None;
+        }
+
+        let mut board = Board::new();
+        board.can_castle = [false; 4];
+        board.side_to_move = color;
+        for (piece, square) in self.pieces.iter().zip(&squares) {
+            board.set(square.0, square.1, *piece);
+        }
+
+        (!in_check(&board, color.opposite())).then_some(board)
+    }
+}
+
+// Distance to mate for every position of one material.
+pub struct DtmTable {
+    layout: Layout,
+    values: Vec<u8>,
+}
+
+impl DtmTable {
+    pub fn name(&self) -> String {
+        material_name(&self.layout.pieces)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn probe_index(&self, index: usize) -> Dtm {
+        Dtm::from_value(self.values[index])
+    }
+
+    // None when the board holds different material.
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        let key = material_key(board);
+        let name = self.name();
+
+        let flip = if key == name {
+            false
+        } else if normalize(&key).as_deref() == Some(&name) {
+            true
+        } else {
+            return None;
+        };
+
+        let (color, squares) = self.layout.placement(board, flip);
+        let index = self.layout.canonical_index(color, &squares)?;
+        Some(self.probe_index(index))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.name();
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + name.len() + self.values.len());
+
+        bytes.extend(MAGIC);
+        bytes.push(name.len() as u8);
+        bytes.extend(name.bytes());
+        bytes.extend(&self.values);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(ErrorKind::InvalidData, message);
+
+        if bytes.len() < 5 || bytes[..4] != MAGIC {
+            return Err(invalid("not a DTM table"));
+        }
+
+        let name_end = 5 + bytes[4] as usize;
+        let pieces = bytes
+            .get(5..name_end)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .and_then(parse_material)
+            .ok_or_else(|| invalid("invalid material"))?;
+
+        let layout = Layout::new(pieces);
+        let values = bytes[name_end..].to_vec();
+        if values.len() != layout.size() {
+            return Err(invalid("table size doesn't match its material"));
+        }
+
+        Ok(Self { layout, values })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+}
+
+// Tables by normalized material name, generated or read from disk.
+#[derive(Default)]
+pub struct DtmTablebase {
+    tables: HashMap<String, DtmTable>,
+}
+
+impl DtmTablebase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Reads every table in a directory.
+    pub fn open(directory: impl AsRef<Path>) -> io::Result<Self> {
+        let mut tablebase = Self::new();
+
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) == Some(EXTENSION) {
+                tablebase.insert(DtmTable::open(path)?);
+            }
+        }
+
+        Ok(tablebase)
+    }
+
+    pub fn insert(&mut self, table: DtmTable) {
+        self.tables.insert(table.name(), table);
+    }
+
+    pub fn table(&self, name: &str) -> Option<&DtmTable> {
+        self.tables.get(&normalize(name)?)
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &DtmTable> {
+        self.tables.values()
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        self.table(&material_key(board))?.probe(board)
+    }
+
+    // Generates a table and every table its captures and promotions lead
+    // to, skipping those already present. Reports each finished table.
+    pub fn generate(&mut self, name: &str, on_table: &mut impl FnMut(&DtmTable)) -> Option<()> {
+        let name = normalize(name)?;
+        if self.tables.contains_key(&name) {
+            return Some(());
+        }
+
+        let pieces = parse_material(&name)?;
+        for successor in successor_materials(&pieces) {
+            self.generate(&successor, on_table)?;
+        }
+
+        let table = generate_table(self, Layout::new(pieces));
+        on_table(&table);
+        self.insert(table);
+        Some(())
+    }
+}
+
+// Materials reachable by one capture or promotion.
+fn successor_materials(pieces: &[Piece]) -> Vec<String> {
+    let mut materials = Vec::new();
+
+    for (index, piece) in pieces.iter().enumerate() {
+        if piece.is_king() {
+            continue;
+        }
+
+        let mut captured = pieces.to_vec();
+        captured.remove(index);
+        materials.push(material_name(&captured));
+
+        if piece.is_pawn() {
+            for kind in 1..5 {
+                let mut promoted = pieces.to_vec();
+                promoted[index] = Piece::from_kind(kind, piece.color()).unwrap();
+                materials.push(material_name(&promoted));
+
+                // Promoting with a capture.
+                for (other, captured) in pieces.iter().enumerate() {
+                    if captured.color() != piece.color() && !captured.is_king() {
+                        let mut both = promoted.clone();
+                        both.remove(other);
+                        materials.push(material_name(&both));
+                    }
+                }
+            }
+        }
+    }
+
+    materials
+}
+
+// Retrograde analysis: checkmates are losses in 0, positions with a move to
+// a loss in n plies are wins in n + 1, and positions whose every move leads
+// to a win are losses one ply after the slowest. Captures and promotions
+// leave the table and are looked up in the smaller tables.
+fn generate_table(tablebase: &DtmTablebase, layout: Layout) -> DtmTable {
+    let size = layout.size();
+    let mut table = DtmTable {
+        values: vec![0; size],
+        layout,
+    };
+
+    // Positions resolved at each ply, and those that can win at a ply by
+    // leaving the table.
+    let mut resolved = vec![Vec::new(); 256];
+    let mut exit_wins = vec![Vec::new(); 256];
+
+    for index in 0..size {
+        let Some(mut board) = table.layout.board(index) else {
+            continue;
+        };
+
+        // Quiet moves stay in the table, so only whether there is one matters.
+        let noisy: Vec<_> = generate_moves(&board, GenType::Noisy)
+            .into_iter()
+            .filter(|chess_move| is_legal(&mut board, chess_move))
+            .collect();
+        let quiet = generate_moves(&board, GenType::Quiet)
+            .iter()
+            .any(|chess_move| is_legal(&mut board, chess_move));
+
+        if noisy.is_empty() && !quiet {
+            if in_check(&board, board.side_to_move) {
+                table.values[index] = 1;
+                resolved[0].push(index);
+            }
+            continue;
+        }
+
+        let mut fastest_win: Option<u32> = None;
+        for chess_move in &noisy {
+            board.make_move(chess_move);
+            let dtm = tablebase.probe(&board);
+            board.unmake_move(chess_move);
+
+            if let Some(Dtm::Loss(plies)) = dtm {
+                fastest_win = Some(fastest_win.map_or(plies + 1, |fastest| fastest.min(plies + 1)));
+            }
+        }
+
+        if let Some(plies) = fastest_win {
+            exit_wins[plies as usize].push(index);
+        } else if !quiet {
+            // Every move leaves the table, so the position is already known.
+            if let Some(plies) = loss_depth(tablebase, &table, &mut board) {
+                table.values[index] = plies as u8 + 1;
+                resolved[plies as usize].push(index);
+            }
+        }
+    }
+
+    for ply in 1..255 {
+        let mut candidates: Vec<_> = std::mem::take(&mut resolved[ply - 1])
+            .into_iter()
+            .flat_map(|index| predecessors(&table, index))
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        if ply % 2 == 1 {
+            for index in candidates
+                .into_iter()
+                .chain(std::mem::take(&mut exit_wins[ply]))
+            {
+                if table.values[index] == 0 {
+                    table.values[index] = ply as u8 + 1;
+                    resolved[ply].push(index);
+                }
+            }
+        } else {
+            for index in candidates {
+                if table.values[index] != 0 {
+                    continue;
+                }
+
+                let mut board = table.layout.board(index).unwrap();
+                if let Some(plies) = loss_depth(tablebase, &table, &mut board) {
+                    table.values[index] = plies as u8 + 1;
+                    resolved[plies as usize].push(index);
+                }
+            }
+        }
+    }
+
+    table
+}
+
+fn predecessors(table: &DtmTable, index: usize) -> Vec<usize> {
+    let board = table.layout.board(index).unwrap();
+
+    unmoves(&board)
+        .iter()
+        .filter_map(|unmove| {
+            let previous = retract(&board, unmove);
+            let (color, squares) = table.layout.placement(&previous, false);
+            table.layout.canonical_index(color, &squares)
+        })
+        .collect()
+}
+
+// Plies to mate when every move already leads to a resolved win for the
+// opponent.
+fn loss_depth(tablebase: &DtmTablebase, table: &DtmTable, board: &mut Board) -> Option<u32> {
+    let color = board.side_to_move;
+    let mut slowest = 0;
+
+    // Legality is checked move by move so that the first move that doesn't
+    // lose ends the search.
+    for chess_move in pseudo_legal_moves(board) {
+        board.make_move(&chess_move);
+        let legal = !in_check(board, color);
+        let dtm = if !legal {
+            None
+        } else if chess_move.is_noisy() {
+            tablebase.probe(board)
+        } else {
+            let (color, squares) = table.layout.placement(board, false);
+            let index = table.layout.canonical_index(color, &squares).unwrap();
+            Some(table.probe_index(index))
+        };
+        board.unmake_move(&chess_move);
+
+        match dtm {
+            Some(Dtm::Win(plies)) => slowest = slowest.max(plies + 1),
+            _ if legal => return None,
+            _ => {}
+        }
+    }
+
+    Some(slowest)
+}
+
+fn is_legal(board: &mut Board, chess_move: &Move) -> bool {
+    let color = board.side_to_move;
+    board.make_move(chess_move);
+    let legal = !in_check(board, color);
+    board.unmake_move(chess_move);
+    legal
+}
+
+pub fn run(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("gen") => {
+            let Some(name) = args.get(1) else {
+                println!("Usage: tablebase gen <material> [directory]");
+                return;
+            };
+            let directory = Path::new(args.get(2).map(String::as_str).unwrap_or("."));
+
+            // Reuse tables written earlier for the smaller materials.
+            let mut tablebase = DtmTablebase::open(directory).unwrap_or_default();
+            let generated = tablebase.generate(name, &mut |table| {
+                let path = directory.join(format!("{}.{}", table.name(), EXTENSION));
+                match table.write(&path) {
+                    Ok(()) => println!("Wrote {} ({} positions)", path.display(), table.len()),
+                    Err(error) => println!("Cannot write {}: {}", path.display(), error),
+                }
+            });
+
+            if generated.is_none() {
+                println!(
+                    "Invalid material {}: expected something like KQvK with at most {} pieces",
+                    name, MAX_PIECES
+                );
+            }
+        }
+        Some("probe") => {
+            let (Some(directory), Some(fen)) = (args.get(1), args.get(2)) else {
+                println!("Usage: tablebase probe <directory> <fen>");
+                return;
+            };
+
+            let Some(board) = Board::from_fen(fen) else {
+                println!("Invalid FEN {}", fen);
+                return;
+            };
+
+            match DtmTablebase::open(directory).map(|tablebase| tablebase.probe(&board)) {
+                Ok(Some(dtm)) => println!("{:?}", dtm),
+                Ok(None) => println!("No table for {}", material_key(&board)),
+                Err(error) => println!("Cannot open {}: {}", directory, error),
+            }
+        }
+        _ => println!("Usage: tablebase gen <material> [directory] | probe <directory> <fen>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(tablebase: &DtmTablebase, fen: &str) -> Dtm {
+        tablebase.probe(&Board::from_fen(fen).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn material_names() {
+        assert_eq!(normalize("KQvK").as_deref(), Some("KQvK"));
+        assert_eq!(normalize("KvKQ").as_deref(), Some("KQvK"));
+        assert_eq!(normalize("KPvKR").as_deref(), Some("KRvKP"));
+        assert_eq!(normalize("KNQvK").as_deref(), Some("KQNvK"));
+        assert_eq!(normalize("KQ"), None);
+        assert_eq!(normalize("KQRvKR"), None);
+
+        let board = Board::from_fen("8/8/8/4k3/8/8/1R6/KQ6 w - - 0 1").unwrap();
+        assert_eq!(material_key(&board), "KQRvK");
+
+        let board = Board::from_fen("8/3p4/8/4k3/8/8/8/KN6 b - - 0 1").unwrap();
+        assert_eq!(material_key(&board), "KNvKP");
+    }
+
+    #[test]
+    fn layout_round_trips_canonical_indices() {
+        let layout = Layout::new(parse_material("KRvKP").unwrap());
+        let squares = [(7, 2), (0, 0), (3, 4), (2, 6)];
+
+        let index = layout.canonical_index(Color::Black, &squares).unwrap();
+        let (color, decoded) = layout.decode(index);
+        assert_eq!(color, Color::Black);
+        assert_eq!(decoded, squares);
+
+        // Mirrored onto the queen side.
+        let mirrored = squares.map(|square| transform(1, square));
+        assert_eq!(layout.canonical_index(Color::Black, &mirrored), Some(index));
+    }
+
+    #[test]
+    fn generates_queen_endings() {
+        let mut tablebase = DtmTablebase::new();
+        let mut generated = Vec::new();
+        tablebase
+            .generate("KvKQ", &mut |table| generated.push(table.name()))
+            .unwrap();
+        assert_eq!(generated, ["KvK", "KQvK"]);
+
+        assert_eq!(
+            probe(&tablebase, "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"),
+            Dtm::Draw
+        );
+        assert_eq!(
+            probe(&tablebase, "7k/8/6K1/8/8/8/8/5Q2 w - - 0 1"),
+            Dtm::Win(1)
+        );
+        assert_eq!(
+            probe(&tablebase, "5Q1k/8/6K1/8/8/8/8/8 b - - 0 1"),
+            Dtm::Loss(0)
+        );
+        assert_eq!(probe(&tablebase, "7k/8/8/8/8/8/8/K7 w - - 0 1"), Dtm::Draw);
+        assert!(matches!(
+            probe(&tablebase, "6qk/8/8/8/8/8/8/K7 w - - 0 1"),
+            Dtm::Loss(_)
+        ));
+
+        // The longest KQK mate takes ten moves, and colours don't matter.
+        let longest = tablebase
+            .table("KQvK")
+            .unwrap()
+            .values
+            .iter()
+            .map(|&value| Dtm::from_value(value))
+            .filter_map(|dtm| match dtm {
+                Dtm::Win(plies) => Some(plies),
+                _ => None,
+            })
+            .max();
+        assert_eq!(longest, Some(19));
+        assert_eq!(
+            probe(&tablebase, "7K/5q2/6k1/8/8/8/8/8 b - - 0 1"),
+            probe(&tablebase, "8/8/8/8/8/6K1/5Q2/7k w - - 0 1")
+        );
+
+        // The tables read back from their bytes.
+        let table = DtmTable::from_bytes(&tablebase.table("KQvK").unwrap().to_bytes()).unwrap();
+        let board = Board::from_fen("7k/8/6K1/8/8/8/8/5Q2 w - - 0 1").unwrap();
+        assert_eq!(table.probe(&board), Some(Dtm::Win(1)));
+    }
+}
@@ -0,0 +1,829 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    board::Board,
+    eval::evaluate,
+    movegen::{in_check, is_illegal_position, legal_moves, variant_status, GameStatus, Move},
+    movepick::{Heuristics, MovePicker},
+    timeman::TimeManager,
+    tt::{Bound, TranspositionTable, TtEntry},
+};
+
+pub const INFINITY: i32 = 32000;
+pub const MATE: i32 = 31000;
+pub const MAX_PLY: usize = 128;
+
+const MAX_DEPTH: u32 = 64;
+const CHECK_INTERVAL: u64 = 2048;
+
+const ASPIRATION_DEPTH: u32 = 4;
+const ASPIRATION_WINDOW: i32 = 25;
+const NULL_MOVE_DEPTH: u32 = 3;
+const REVERSE_FUTILITY_DEPTH: u32 = 6;
+const REVERSE_FUTILITY_MARGIN: i32 = 80;
+const FUTILITY_DEPTH: u32 = 3;
+const FUTILITY_MARGIN: i32 = 100;
+const LMR_DEPTH: u32 = 3;
+const LMR_MOVES: u32 = 3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u32>,
+    pub infinite: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchOptions {
+    pub move_overhead: u64,
+    pub hash: usize,
+    pub threads: usize,
+    pub multipv: usize,
+    pub move_ordering: bool,
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub futility: bool,
+    pub reverse_futility: bool,
+    pub aspiration_windows: bool,
+    pub check_extensions: bool,
+    pub pvs: bool,
+}
+
+impl SearchOptions {
+    // Plain alpha-beta with move ordering, the baseline for A/B comparisons.
+    pub fn without_pruning() -> Self {
+        Self {
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            reverse_futility: false,
+            aspiration_windows: false,
+            check_extensions: false,
+            pvs: false,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            move_overhead: 30,
+            hash: 16,
+            threads: 1,
+            multipv: 1,
+            move_ordering: true,
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            reverse_futility: true,
+            aspiration_windows: true,
+            check_extensions: true,
+            pvs: true,
+        }
+    }
+}
+
+pub struct SearchInfo {
+    pub multipv: usize,
+    pub depth: u32,
+    pub seldepth: u32,
+    pub score: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+}
+
+pub struct Searcher {
+    board: Board,
+    limits: SearchLimits,
+    options: SearchOptions,
+    stop: Arc<AtomicBool>,
+    time: TimeManager,
+    tt: Arc<TranspositionTable>,
+    heuristics: Heuristics,
+    move_stack: Vec<Option<Move>>,
+    nodes: u64,
+    seldepth: u32,
+    pv: Vec<Vec<Move>>,
+    helper_id: usize,
+    helper_nodes: Arc<AtomicU64>,
+    excluded_root_moves: Vec<Move>,
+}
+
+impl Searcher {
+    pub fn new(
+        board: Board,
+        limits: SearchLimits,
+        options: SearchOptions,
+        stop: Arc<AtomicBool>,
+    ) -> Self {
+        let tt = Arc::new(TranspositionTable::new(options.hash));
+        Self::with_tt(board, limits, options, stop, tt)
+    }
+
+    // Searches with a table that outlives the search, such as one kept
+    // between moves of a game.
+    pub fn with_tt(
+        board: Board,
+        limits: SearchLimits,
+        options: SearchOptions,
+        stop: Arc<AtomicBool>,
+        tt: Arc<TranspositionTable>,
+    ) -> Self {
+        let time = TimeManager::new(&limits, board.side_to_move, options.move_overhead);
+
+        Self {
+            board,
+            limits,
+            options,
+            stop,
+            time,
+            tt,
+            heuristics: Heuristics::new(MAX_PLY),
+            move_stack: vec![None; MAX_PLY + 1],
+            nodes: 0,
+            seldepth: 0,
+            pv: vec![Vec::new(); MAX_PLY + 1],
+            helper_id: 0,
+            helper_nodes: Arc::new(AtomicU64::new(0)),
+            excluded_root_moves: Vec::new(),
+        }
+    }
+
+    // Lazy SMP: helper threads search the same root without reporting
+    // anything, and only help by filling the shared transposition table.
+    pub fn search(&mut self, on_info: impl FnMut(&SearchInfo)) -> Option<Move> {
+        self.time = TimeManager::new(
+            &self.limits,
+            self.board.side_to_move,
+            self.options.move_overhead,
+        );
+        self.nodes = 0;
+        self.helper_nodes.store(0, Ordering::Relaxed);
+
+        if self.options.threads <= 1 {
+            return self.iterate(on_info);
+        }
+
+        let helper_stop = Arc::new(AtomicBool::new(false));
+        thread::scope(|scope| {
+            for helper_id in 1..self.options.threads {
+                let limits = SearchLimits {
+                    infinite: true,
+                    ..Default::default()
+                };
+                let options = SearchOptions {
+                    threads: 1,
+                    multipv: 1,
+                    ..self.options
+                };
+
+                let mut helper = Searcher::with_tt(
+                    self.board.clone(),
+                    limits,
+                    options,
+                    helper_stop.clone(),
+                    self.tt.clone(),
+                );
+                helper.helper_id = helper_id;
+                helper.helper_nodes = self.helper_nodes.clone();
+
+                scope.spawn(move || helper.iterate(|_| {}));
+            }
+
+            let best_move = self.iterate(on_info);
+            helper_stop.store(true, Ordering::Relaxed);
+            best_move
+        })
+    }
+
+    fn iterate(&mut self, mut on_info: impl FnMut(&SearchInfo)) -> Option<Move> {
+        let root_moves = legal_moves(&mut self.board);
+        let mut best_move = root_moves.first().copied();
+        if root_moves.len() <= 1 && !self.limits.infinite && self.limits.depth.is_none() {
+            return best_move;
+        }
+
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        let line_count = self.options.multipv.clamp(1, root_moves.len().max(1));
+        let mut scores = vec![0; line_count];
+
+        // Odd helpers skip the first iteration so threads drift apart.
+        let start_depth = (1 + self.helper_id as u32 % 2).min(max_depth);
+        'deepening: for depth in start_depth..=max_depth {
+            // Each further line is the best move once the earlier ones are
+            // excluded from the root.
+            let mut lines = Vec::new();
+            self.excluded_root_moves.clear();
+
+            for &previous in &scores {
+                self.seldepth = 0;
+
+                let score = self.aspiration(depth, previous);
+                if self.stop.load(Ordering::Relaxed) && depth > 1 {
+                    break 'deepening;
+                }
+
+                let Some(&first) = self.pv[0].first() else {
+                    break;
+                };
+                self.excluded_root_moves.push(first);
+                lines.push((score, self.seldepth, self.pv[0].clone()));
+            }
+            self.excluded_root_moves.clear();
+
+            if lines.is_empty() {
+                break;
+            }
+            lines.sort_by_key(|(score, _, _)| -score);
+            for (line, (score, seldepth, pv)) in lines.iter().enumerate() {
+                scores[line] = *score;
+                on_info(&SearchInfo {
+                    multipv: line + 1,
+                    depth,
+                    seldepth: *seldepth,
+                    score: *score,
+                    nodes: self.nodes(),
+                    time: self.time.elapsed(),
+                    pv: pv.clone(),
+                });
+            }
+
+            let previous_best_move = best_move;
+            best_move = lines[0].2.first().copied();
+
+            let score = lines[0].0;
+            self.time
+                .update(depth > 1 && best_move != previous_best_move, score);
+            if self.should_stop()
+                || self.time.soft_limit_reached()
+                || (line_count == 1 && score.abs() >= MATE - MAX_PLY as i32)
+            {
+                break;
+            }
+        }
+
+        best_move
+    }
+
+    // Searches a narrow window around the previous score, widening it on the
+    // side that failed until the score falls inside.
+    fn aspiration(&mut self, depth: u32, previous: i32) -> i32 {
+        if !self.options.aspiration_windows
+            || depth < ASPIRATION_DEPTH
+            || previous.abs() >= MATE - MAX_PLY as i32
+        {
+            return self.negamax(depth, 0, -INFINITY, INFINITY);
+        }
+
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = (previous - delta).max(-INFINITY);
+        let mut beta = (previous + delta).min(INFINITY);
+
+        loop {
+            let score = self.negamax(depth, 0, alpha, beta);
+            if self.stop.load(Ordering::Relaxed) {
+                return score;
+            }
+
+            delta *= 2;
+            if score <= alpha {
+                alpha = (score - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (score + delta).min(INFINITY);
+            } else {
+                return score;
+            }
+        }
+    }
+
+    // Nodes searched by this thread and, roughly, by its helpers.
+    pub fn nodes(&self) -> u64 {
+        self.nodes + self.helper_nodes.load(Ordering::Relaxed)
+    }
+
+    fn should_stop(&self) -> bool {
+        if self.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        if let Some(nodes) = self.limits.nodes {
+            if self.nodes >= nodes {
+                return true;
+            }
+        }
+
+        self.time.hard_limit_reached()
+    }
+
+    fn poll_stop(&mut self) -> bool {
+        if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            if self.helper_id > 0 {
+                self.helper_nodes
+                    .fetch_add(CHECK_INTERVAL, Ordering::Relaxed);
+            }
+
+            if self.should_stop() {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    fn score_to_tt(score: i32, ply: usize) -> i32 {
+        if score >= MATE - MAX_PLY as i32 {
+            score + ply as i32
+        } else if score <= -MATE + MAX_PLY as i32 {
+            score - ply as i32
+        } else {
+            score
+        }
+    }
+
+    fn score_from_tt(score: i32, ply: usize) -> i32 {
+        if score >= MATE - MAX_PLY as i32 {
+            score - ply as i32
+        } else if score <= -MATE + MAX_PLY as i32 {
+            score + ply as i32
+        } else {
+            score
+        }
+    }
+
+    // The score of a decided game for the side to move.
+    fn status_score(&self, status: GameStatus, ply: usize) -> i32 {
+        match status {
+            GameStatus::Checkmate(color) | GameStatus::VariantWin(color)
+                if color == self.board.side_to_move =>
+            {
+                MATE - ply as i32
+            }
+            GameStatus::Checkmate(_) | GameStatus::VariantWin(_) => -MATE + ply as i32,
+            _ => 0,
+        }
+    }
+
+    // The score of a game a variant rule has already decided, like a mate.
+    fn variant_score(&mut self, ply: usize) -> Option<i32> {
+        let status = variant_status(&mut self.board)?;
+        Some(self.status_score(status, ply))
+    }
+
+    fn negamax(&mut self, mut depth: u32, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        let color = self.board.side_to_move;
+        let checked = in_check(&self.board, color);
+        if checked && self.options.check_extensions {
+            depth += 1;
+        }
+
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiescence(ply, alpha, beta);
+        }
+
+        self.nodes += 1;
+        self.pv[ply].clear();
+
+        if ply > 0 {
+            if self.poll_stop() {
+                return 0;
+            }
+
+            if self.board.is_repetition() || self.board.is_fifty_move_draw() {
+                return 0;
+            }
+
+            if let Some(score) = self.variant_score(ply) {
+                return score;
+            }
+        }
+
+        let entry = self.tt.probe(self.board.hash);
+        if let Some(entry) = entry {
+            let score = Self::score_from_tt(entry.score, ply);
+            if ply > 0 && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => {}
+                }
+            }
+        }
+
+        let original_alpha = alpha;
+        let is_pv = beta - alpha > 1;
+        let previous_move = if ply > 0 {
+            self.move_stack[ply - 1]
+        } else {
+            None
+        };
+        let static_eval = if checked {
+            -INFINITY
+        } else {
+            evaluate(&self.board)
+        };
+
+        if ply > 0 && !is_pv && !checked {
+            if self.options.reverse_futility
+                && depth <= REVERSE_FUTILITY_DEPTH
+                && static_eval - REVERSE_FUTILITY_MARGIN * depth as i32 >= beta
+            {
+                return static_eval;
+            }
+
+            // Never pass twice in a row, and never with only pawns left where
+            // zugzwang makes passing better than any real move.
+            if self.options.null_move
+                && depth >= NULL_MOVE_DEPTH
+                && static_eval >= beta
+                && previous_move.is_some()
+                && self.board.has_non_pawn_material(color)
+            {
+                let reduction = 3 + depth / 6;
+
+                self.board.make_null_move();
+                self.move_stack[ply] = None;
+                let score =
+                    -self.negamax(depth.saturating_sub(reduction), ply + 1, -beta, -beta + 1);
+                self.board.unmake_null_move();
+
+                if self.stop.load(Ordering::Relaxed) {
+                    return 0;
+                }
+
+                if score >= beta {
+                    return if score >= MATE - MAX_PLY as i32 {
+                        beta
+                    } else {
+                        score
+                    };
+                }
+            }
+        }
+
+        let futile = self.options.futility
+            && ply > 0
+            && !is_pv
+            && !checked
+            && depth <= FUTILITY_DEPTH
+            && static_eval + FUTILITY_MARGIN * depth as i32 <= alpha;
+
+        let mut picker = MovePicker::new(
+            entry.and_then(|entry| entry.best_move),
+            self.heuristics.killers(ply),
+            self.heuristics.counter(previous_move),
+            self.options.move_ordering,
+        );
+
+        let mut legal_count = 0;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut quiets_tried = Vec::new();
+
+        while let Some(chess_move) = picker.next(&mut self.board, &self.heuristics) {
+            if ply == 0 && self.excluded_root_moves.contains(&chess_move) {
+                continue;
+            }
+
+            self.board.make_move(&chess_move);
+            if is_illegal_position(&self.board, color) {
+                self.board.unmake_move(&chess_move);
+                continue;
+            }
+            legal_count += 1;
+            self.move_stack[ply] = Some(chess_move);
+
+            let quiet = !chess_move.is_noisy();
+            let gives_check = in_check(&self.board, color.opposite());
+
+            // Quiet moves can't lift a hopeless static eval above alpha.
+            if futile && legal_count > 1 && quiet && !gives_check {
+                self.board.unmake_move(&chess_move);
+                continue;
+            }
+
+            let score = if legal_count == 1 {
+                -self.negamax(depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                let reduction = if self.options.late_move_reductions
+                    && depth >= LMR_DEPTH
+                    && legal_count > LMR_MOVES
+                    && quiet
+                    && !checked
+                    && !gives_check
+                {
+                    let reduction = (depth as f64).ln() * (legal_count as f64).ln() / 2.0;
+                    (reduction as u32).clamp(1, depth - 2)
+                } else {
+                    0
+                };
+
+                // Later moves are expected to fail low, so a null window is
+                // enough to prove it; only a surprise gets the full window.
+                let window = if self.options.pvs { alpha + 1 } else { beta };
+
+                let mut score = -self.negamax(depth - 1 - reduction, ply + 1, -window, -alpha);
+                if reduction > 0 && score > alpha {
+                    score = -self.negamax(depth - 1, ply + 1, -window, -alpha);
+                }
+                if window != beta && score > alpha && score < beta {
+                    score = -self.negamax(depth - 1, ply + 1, -beta, -alpha);
+                }
+
+                score
+            };
+            self.board.unmake_move(&chess_move);
+
+            if self.stop.load(Ordering::Relaxed) {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(chess_move);
+            }
+
+            if score > alpha {
+                alpha = score;
+
+                let (head, tail) = self.pv.split_at_mut(ply + 1);
+                head[ply].clear();
+                head[ply].push(chess_move);
+                head[ply].extend_from_slice(&tail[0]);
+            }
+
+            if alpha >= beta {
+                if quiet {
+                    self.heuristics.update_quiet(
+                        ply,
+                        previous_move,
+                        &chess_move,
+                        &quiets_tried,
+                        depth,
+                    );
+                }
+                break;
+            }
+
+            if quiet {
+                quiets_tried.push(chess_move);
+            }
+        }
+
+        // Mate or stalemate, unless the variant rules otherwise.
+        if legal_count == 0 {
+            let status = self.board.variant.no_moves_status(&self.board);
+            return self.status_score(status, ply);
+        }
+
+        // A root searched with moves excluded has no score worth sharing.
+        if ply == 0 && !self.excluded_root_moves.is_empty() {
+            return best_score;
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(TtEntry {
+            key: self.board.hash,
+            best_move,
+            score: Self::score_to_tt(best_score, ply),
+            depth,
+            bound,
+        });
+
+        best_score
+    }
+
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply as u32);
+        self.pv[ply].clear();
+
+        if self.poll_stop() {
+            return 0;
+        }
+
+        if let Some(score) = self.variant_score(ply) {
+            return score;
+        }
+
+        let stand_pat = evaluate(&self.board);
+        if ply >= MAX_PLY || stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let color = self.board.side_to_move;
+        let mut picker = MovePicker::new_noisy(self.options.move_ordering);
+
+        while let Some(chess_move) = picker.next(&mut self.board, &self.heuristics) {
+            if chess_move
+                .promotion()
+                .is_some_and(|piece| piece.kind() != 4)
+            {
+                continue;
+            }
+
+            self.board.make_move(&chess_move);
+            if is_illegal_position(&self.board, color) {
+                self.board.unmake_move(&chess_move);
+                continue;
+            }
+
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.board.unmake_move(&chess_move);
+
+            if self.stop.load(Ordering::Relaxed) {
+                return 0;
+            }
+
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        alpha
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn searcher(fen: &str, depth: u32, options: SearchOptions) -> Searcher {
+        let board = Board::from_fen(fen).unwrap();
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        };
+
+        Searcher::new(board, limits, options, Arc::new(AtomicBool::new(false)))
+    }
+
+    fn best_move(fen: &str, depth: u32) -> Option<String> {
+        let mut searcher = searcher(fen, depth, SearchOptions::default());
+        searcher.search(|_| {}).map(|m| m.to_uci())
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        assert_eq!(
+            best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2),
+            Some("a1a8".to_string())
+        );
+    }
+
+    #[test]
+    fn wins_hanging_queen() {
+        assert_eq!(
+            best_move("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 2),
+            Some("d2d5".to_string())
+        );
+    }
+
+    #[test]
+    fn each_pruning_technique_alone_finds_tactics() {
+        let toggles: [fn(&mut SearchOptions); 7] = [
+            |options| options.null_move = true,
+            |options| options.late_move_reductions = true,
+            |options| options.futility = true,
+            |options| options.reverse_futility = true,
+            |options| options.aspiration_windows = true,
+            |options| options.check_extensions = true,
+            |options| options.pvs = true,
+        ];
+
+        for toggle in toggles {
+            let mut options = SearchOptions::without_pruning();
+            toggle(&mut options);
+
+            let mut mate = searcher("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 4, options);
+            assert_eq!(mate.search(|_| {}).map(|m| m.to_uci()), Some("a1a8".into()));
+
+            let mut queen = searcher("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 4, options);
+            assert_eq!(
+                queen.search(|_| {}).map(|m| m.to_uci()),
+                Some("d2d5".into())
+            );
+        }
+    }
+
+    #[test]
+    fn pruning_reduces_node_count() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+
+        let mut plain = searcher(fen, 5, SearchOptions::without_pruning());
+        plain.search(|_| {});
+
+        let mut pruned = searcher(fen, 5, SearchOptions::default());
+        pruned.search(|_| {});
+
+        assert!(pruned.nodes() < plain.nodes());
+    }
+
+    #[test]
+    fn lazy_smp_finds_tactics() {
+        let options = SearchOptions {
+            threads: 4,
+            ..Default::default()
+        };
+
+        let mut mate = searcher("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 5, options);
+        assert_eq!(mate.search(|_| {}).map(|m| m.to_uci()), Some("a1a8".into()));
+
+        let mut queen = searcher("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 5, options);
+        assert_eq!(
+            queen.search(|_| {}).map(|m| m.to_uci()),
+            Some("d2d5".into())
+        );
+    }
+
+    #[test]
+    fn single_thread_search_is_deterministic() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+
+        let runs: Vec<_> = (0..2)
+            .map(|_| {
+                let mut searcher = searcher(fen, 4, SearchOptions::default());
+                let best_move = searcher.search(|_| {});
+                (best_move, searcher.nodes())
+            })
+            .collect();
+
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[test]
+    fn shared_table_persists_between_searches() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+        let board = Board::from_fen(fen).unwrap();
+        let tt = Arc::new(TranspositionTable::new(1));
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..Default::default()
+        };
+
+        let mut nodes = Vec::new();
+        for _ in 0..2 {
+            let mut searcher = Searcher::with_tt(
+                board.clone(),
+                limits,
+                SearchOptions::default(),
+                Arc::new(AtomicBool::new(false)),
+                tt.clone(),
+            );
+            searcher.search(|_| {});
+            nodes.push(searcher.nodes());
+        }
+
+        assert!(nodes[1] < nodes[0]);
+    }
+
+    #[test]
+    fn multipv_reports_distinct_root_moves() {
+        let options = SearchOptions {
+            multipv: 3,
+            ..Default::default()
+        };
+        let mut searcher = searcher("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3, options);
+
+        let mut lines = Vec::new();
+        let best_move = searcher.search(|info| {
+            if info.depth == 3 {
+                lines.push((info.multipv, info.score, info.pv[0]));
+            }
+        });
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines.iter().map(|line| line.0).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert_eq!(Some(lines[0].2), best_move);
+        assert_eq!(lines[0].2.to_uci(), "a1a8");
+        assert!(lines.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert!(lines[1..].iter().all(|line| line.2 != lines[0].2));
+        assert_ne!(lines[1].2, lines[2].2);
+    }
+}
@@ -0,0 +1,177 @@
+// Match statistics: the Elo difference a score implies and the sequential
+// probability ratio test deciding between two Elo hypotheses.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    400.0 * (score / (1.0 - score)).log10()
+}
+
+impl Score {
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    pub fn add(&mut self, points: f64) {
+        if points > 0.5 {
+            self.wins += 1;
+        } else if points < 0.5 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    // The mean score per game and its variance, from the trinomial
+    // distribution of results.
+    fn mean_and_variance(&self) -> Option<(f64, f64)> {
+        let games = self.games() as f64;
+        if games == 0.0 {
+            return None;
+        }
+
+        let wins = self.wins as f64 / games;
+        let draws = self.draws as f64 / games;
+        let mean = wins + draws / 2.0;
+        let variance = wins + draws / 4.0 - mean * mean;
+        Some((mean, variance))
+    }
+
+    // The Elo difference with its 95% confidence margin. Infinite when one
+    // side scored everything.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let (mean, variance) = self.mean_and_variance()?;
+        let elo = elo_from_score(mean);
+        if !elo.is_finite() {
+            return Some((elo, f64::INFINITY));
+        }
+
+        let deviation = (variance / self.games() as f64).sqrt();
+
+        let low = elo_from_score((mean - 1.96 * deviation).clamp(0.0, 1.0));
+        let high = elo_from_score((mean + 1.96 * deviation).clamp(0.0, 1.0));
+        Some((elo, (high - low) / 2.0))
+    }
+
+    // Log-likelihood ratio of elo1 against elo0, using the usual normal
+    // approximation. Zero until both wins and losses have happened.
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let Some((mean, variance)) = self.mean_and_variance() else {
+            return 0.0;
+        };
+        if self.wins == 0 || self.losses == 0 || variance <= 0.0 {
+            return 0.0;
+        }
+
+        let (score0, score1) = (expected_score(elo0), expected_score(elo1));
+        let variance = variance / self.games() as f64;
+        (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtResult {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+impl Sprt {
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn test(&self, score: &Score) -> SprtResult {
+        let llr = score.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+
+        if llr >= upper {
+            SprtResult::AcceptH1
+        } else if llr <= lower {
+            SprtResult::AcceptH0
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_from_results() {
+        let even = Score {
+            wins: 10,
+            losses: 10,
+            draws: 20,
+        };
+        let (elo, margin) = even.elo().unwrap();
+        assert!(elo.abs() < 1e-9);
+        assert!(margin > 0.0);
+
+        // 75% is about +191 Elo.
+        let strong = Score {
+            wins: 50,
+            losses: 0,
+            draws: 50,
+        };
+        assert!((strong.elo().unwrap().0 - 190.85).abs() < 0.01);
+        assert_eq!(Score::default().elo(), None);
+    }
+
+    #[test]
+    fn sprt_decisions() {
+        let sprt = Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        };
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001);
+        assert!((upper - 2.944).abs() < 0.001);
+
+        let better = Score {
+            wins: 600,
+            losses: 400,
+            draws: 1000,
+        };
+        assert!(better.llr(0.0, 10.0) > 0.0);
+        assert_eq!(sprt.test(&better), SprtResult::AcceptH1);
+
+        let worse = Score {
+            wins: 400,
+            losses: 600,
+            draws: 1000,
+        };
+        assert_eq!(sprt.test(&worse), SprtResult::AcceptH0);
+
+        let early = Score {
+            wins: 3,
+            losses: 2,
+            draws: 5,
+        };
+        assert_eq!(sprt.test(&early), SprtResult::Continue);
+    }
+}
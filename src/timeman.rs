@@ -0,0 +1,261 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{piece::Color, search::SearchLimits};
+
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+const MAX_SCALE: f64 = 3.0;
+const FAIL_LOW_MARGIN: i32 = 30;
+
+pub trait Clock: Send {
+    fn elapsed(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// A clock that only moves when told to, so time decisions can be replayed
+// deterministically.
+#[derive(Clone, Default)]
+pub struct MockClock {
+    elapsed: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn advance(&self, millis: u64) {
+        self.elapsed.fetch_add(millis, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.elapsed.load(Ordering::Relaxed))
+    }
+}
+
+pub struct TimeManager {
+    clock: Box<dyn Clock>,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    scale: f64,
+    instability: f64,
+    previous_score: Option<i32>,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, color: Color, move_overhead: u64) -> Self {
+        Self::with_clock(limits, color, move_overhead, Box::new(SystemClock::new()))
+    }
+
+    pub fn with_clock(
+        limits: &SearchLimits,
+        color: Color,
+        move_overhead: u64,
+        clock: Box<dyn Clock>,
+    ) -> Self {
+        let (soft_limit, hard_limit) = match Self::limits(limits, color, move_overhead) {
+            Some((soft, hard)) => (
+                Some(Duration::from_millis(soft)),
+                Some(Duration::from_millis(hard)),
+            ),
+            None => (None, None),
+        };
+
+        Self {
+            clock,
+            soft_limit,
+            hard_limit,
+            scale: 1.0,
+            instability: 0.0,
+            previous_score: None,
+        }
+    }
+
+    // Soft and hard limits in milliseconds, if the search is bounded by the clock.
+    fn limits(limits: &SearchLimits, color: Color, move_overhead: u64) -> Option<(u64, u64)> {
+        if limits.infinite {
+            return None;
+        }
+
+        if let Some(movetime) = limits.movetime {
+            let movetime = movetime.saturating_sub(move_overhead).max(1);
+            return Some((movetime, movetime));
+        }
+
+        let (time, increment) = match color {
+            Color::White => (limits.wtime?, limits.winc.unwrap_or(0)),
+            Color::Black => (limits.btime?, limits.binc.unwrap_or(0)),
+        };
+
+        let available = time.saturating_sub(move_overhead).max(1);
+        let moves_to_go = limits
+            .movestogo
+            .map(|moves| moves as u64)
+            .unwrap_or(DEFAULT_MOVES_TO_GO)
+            .max(1);
+
+        let soft = (available / moves_to_go + increment * 3 / 4).min(available * 7 / 10);
+        let hard = (soft * 3).min(available * 8 / 10).max(soft);
+
+        Some((soft.max(1), hard.max(1)))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.clock.elapsed()
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        let soft_limit = self.soft_limit?.mul_f64(self.scale);
+        Some(soft_limit.min(self.hard_limit?))
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        self.hard_limit
+    }
+
+    // Called after every completed iteration with its best move and score.
+    // Unstable best moves and falling scores buy the search extra time.
+    pub fn update(&mut self, best_move_changed: bool, score: i32) {
+        if best_move_changed {
+            self.instability += 1.0;
+        } else {
+            self.instability *= 0.5;
+        }
+
+        let fail_low = self
+            .previous_score
+            .is_some_and(|previous| score <= previous - FAIL_LOW_MARGIN);
+        self.previous_score = Some(score);
+
+        let fail_low_scale = if fail_low { 1.5 } else { 1.0 };
+        self.scale = ((1.0 + 0.3 * self.instability) * fail_low_scale).min(MAX_SCALE);
+    }
+
+    // Starting another iteration past the soft limit is unlikely to finish.
+    pub fn soft_limit_reached(&self) -> bool {
+        matches!(self.soft_limit(), Some(limit) if self.elapsed() >= limit)
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        matches!(self.hard_limit, Some(limit) if self.elapsed() >= limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(limits: SearchLimits, color: Color) -> (TimeManager, MockClock) {
+        let clock = MockClock::default();
+        let manager = TimeManager::with_clock(&limits, color, 0, Box::new(clock.clone()));
+        (manager, clock)
+    }
+
+    #[test]
+    fn limits_from_clock() {
+        let limits = SearchLimits {
+            wtime: Some(60000),
+            btime: Some(3000),
+            winc: Some(1000),
+            movestogo: Some(20),
+            ..Default::default()
+        };
+
+        let (white, _) = manager(limits, Color::White);
+        assert_eq!(white.soft_limit(), Some(Duration::from_millis(3750)));
+        assert_eq!(white.hard_limit(), Some(Duration::from_millis(11250)));
+
+        let (black, _) = manager(limits, Color::Black);
+        assert_eq!(black.soft_limit(), Some(Duration::from_millis(150)));
+        assert_eq!(black.hard_limit(), Some(Duration::from_millis(450)));
+    }
+
+    #[test]
+    fn last_move_before_time_control_keeps_a_reserve() {
+        let limits = SearchLimits {
+            wtime: Some(10000),
+            movestogo: Some(1),
+            ..Default::default()
+        };
+
+        let (manager, _) = manager(limits, Color::White);
+        assert_eq!(manager.soft_limit(), Some(Duration::from_millis(7000)));
+        assert_eq!(manager.hard_limit(), Some(Duration::from_millis(8000)));
+    }
+
+    #[test]
+    fn unbounded_searches_have_no_limits() {
+        let (manager, clock) = manager(
+            SearchLimits {
+                infinite: true,
+                wtime: Some(1000),
+                ..Default::default()
+            },
+            Color::White,
+        );
+        clock.advance(1_000_000);
+
+        assert!(!manager.soft_limit_reached());
+        assert!(!manager.hard_limit_reached());
+    }
+
+    #[test]
+    fn movetime_subtracts_overhead() {
+        let limits = SearchLimits {
+            movetime: Some(1000),
+            ..Default::default()
+        };
+        let manager =
+            TimeManager::with_clock(&limits, Color::Black, 50, Box::new(MockClock::default()));
+
+        assert_eq!(manager.soft_limit(), Some(Duration::from_millis(950)));
+        assert_eq!(manager.hard_limit(), Some(Duration::from_millis(950)));
+    }
+
+    #[test]
+    fn instability_and_fail_low_extend_soft_limit() {
+        let limits = SearchLimits {
+            wtime: Some(60000),
+            movestogo: Some(20),
+            ..Default::default()
+        };
+        let (mut manager, clock) = manager(limits, Color::White);
+
+        manager.update(false, 20);
+        clock.advance(3100);
+        assert!(manager.soft_limit_reached());
+
+        manager.update(true, 20);
+        assert!(!manager.soft_limit_reached());
+
+        clock.advance(900);
+        assert!(manager.soft_limit_reached());
+
+        manager.update(false, -40);
+        assert!(!manager.soft_limit_reached());
+        assert!(!manager.hard_limit_reached());
+
+        clock.advance(5000);
+        assert!(manager.hard_limit_reached());
+    }
+}
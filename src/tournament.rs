@@ -0,0 +1,762 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::Path,
+};
+
+use crate::{
+    arena::{
+        parse_openings, play_games, player_names, EngineConfig, Opening, ScheduledGame, TimeControl,
+    },
+    pgn::GameResult,
+    sprt::Score,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    // Everyone plays everyone once per cycle.
+    RoundRobin,
+    // The first player plays each of the others once per cycle.
+    Gauntlet,
+    // Players with similar scores meet, without rematches where possible.
+    Swiss,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Self::RoundRobin => "roundrobin",
+            Self::Gauntlet => "gauntlet",
+            Self::Swiss => "swiss",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "roundrobin" => Some(Self::RoundRobin),
+            "gauntlet" => Some(Self::Gauntlet),
+            "swiss" => Some(Self::Swiss),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TournamentOptions {
+    pub format: Format,
+    // Cycles for round-robin and gauntlet tournaments, rounds for Swiss.
+    pub rounds: usize,
+    // Games per pairing, alternating colours from the same opening.
+    pub games_per_pairing: usize,
+}
+
+impl Default for TournamentOptions {
+    fn default() -> Self {
+        Self {
+            format: Format::RoundRobin,
+            rounds: 1,
+            games_per_pairing: 2,
+        }
+    }
+}
+
+// A round's pairings and the player sitting out, if any.
+pub type Round = (Vec<(usize, usize)>, Option<usize>);
+
+fn white_points(result: GameResult) -> f64 {
+    match result {
+        GameResult::WhiteWins => 1.0,
+        GameResult::BlackWins => 0.0,
+        _ => 0.5,
+    }
+}
+
+// Pairs each player in ranking order with the highest ranked opponent they
+// haven't met, backtracking when that leaves the rest unpairable.
+fn pair_ranking(
+    ranking: &[usize],
+    met: &impl Fn(usize, usize) -> bool,
+) -> Option<Vec<(usize, usize)>> {
+    let Some((&player, rest)) = ranking.split_first() else {
+        return Some(Vec::new());
+    };
+
+    for (index, &opponent) in rest.iter().enumerate() {
+        if met(player, opponent) {
+            continue;
+        }
+
+        let mut others = rest.to_vec();
+        others.remove(index);
+        if let Some(mut pairs) = pair_ranking(&others, met) {
+            pairs.insert(0, (player, opponent));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayedGame {
+    pub round: usize,
+    pub game: usize,
+    pub white: usize,
+    pub black: usize,
+    pub result: GameResult,
+}
+
+// The schedule and every finished game, which is all that's needed to
+// resume: Swiss pairings only depend on the results of earlier rounds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tournament {
+    pub names: Vec<String>,
+    pub options: TournamentOptions,
+    pub games: Vec<PlayedGame>,
+}
+
+impl Tournament {
+    pub fn new(names: Vec<String>, options: TournamentOptions) -> Self {
+        Self {
+            names,
+            options,
+            games: Vec::new(),
+        }
+    }
+
+    pub fn round_count(&self) -> usize {
+        let players = self.names.len();
+        match self.options.format {
+            // With an odd number of players, one sits out each round.
+            Format::RoundRobin => self.options.rounds * (players - 1 + players % 2),
+            Format::Gauntlet | Format::Swiss => self.options.rounds,
+        }
+    }
+
+    // The pairings of a round, and the player with a bye if any. The first
+    // player of a pairing takes White in the pairing's first game.
+    pub fn pairings(&self, round: usize) -> Round {
+        self.rounds(round + 1).pop().unwrap()
+    }
+
+    // The pairings of every round up to `count`, each Swiss round depending
+    // on the ones before.
+    fn rounds(&self, count: usize) -> Vec<Round> {
+        let mut rounds = Vec::new();
+        for round in 0..count {
+            let pairings = match self.options.format {
+                Format::Swiss => self.swiss_pairings(round, &rounds),
+                _ => self.fixed_pairings(round),
+            };
+            rounds.push(pairings);
+        }
+        rounds
+    }
+
+    fn fixed_pairings(&self, round: usize) -> Round {
+        let players = self.names.len();
+        match self.options.format {
+            Format::RoundRobin => {
+                // Circle method: the last seat stays, the others rotate. An
+                // odd field gets an empty seat.
+                let seats = players + players % 2;
+                let rounds_per_cycle = seats - 1;
+                let (cycle, round) = (round / rounds_per_cycle, round % rounds_per_cycle);
+                let seat = |index: usize| {
+                    if index == seats - 1 {
+                        seats - 1
+                    } else {
+                        (index + round) % rounds_per_cycle
+                    }
+                };
+
+                let mut pairings = Vec::new();
+                let mut bye = None;
+                for index in 0..seats / 2 {
+                    let (mut first, mut second) = (seat(index), seat(seats - 1 - index));
+                    if (index == 0 && round % 2 == 1) || cycle % 2 == 1 {
+                        (first, second) = (second, first);
+                    }
+
+                    match (first < players, second < players) {
+                        (true, true) => pairings.push((first, second)),
+                        (true, false) => bye = Some(first),
+                        (false, _) => bye = Some(second),
+                    }
+                }
+                (pairings, bye)
+            }
+            Format::Gauntlet => {
+                let pairings = (1..players)
+                    .map(|other| {
+                        if round.is_multiple_of(2) {
+                            (0, other)
+                        } else {
+                            (other, 0)
+                        }
+                    })
+                    .collect();
+                (pairings, None)
+            }
+            Format::Swiss => unreachable!(),
+        }
+    }
+
+    fn swiss_pairings(&self, round: usize, previous: &[Round]) -> Round {
+        let earlier: Vec<_> = self
+            .games
+            .iter()
+            .filter(|game| game.round < round)
+            .collect();
+        let points = self.points(round, previous);
+        let met = |a: usize, b: usize| {
+            earlier.iter().any(|game| {
+                (game.white, game.black) == (a, b) || (game.white, game.black) == (b, a)
+            })
+        };
+
+        // Ranked by points, ties broken by seeding.
+        let mut ranking: Vec<_> = (0..self.names.len()).collect();
+        ranking.sort_by(|&a, &b| points[b].total_cmp(&points[a]).then(a.cmp(&b)));
+
+        // The lowest ranked player who hasn't had a bye sits out.
+        let mut bye = None;
+        if ranking.len() % 2 == 1 {
+            let position = ranking
+                .iter()
+                .rposition(|player| !previous.iter().any(|(_, bye)| *bye == Some(*player)))
+                .unwrap_or(ranking.len() - 1);
+            bye = Some(ranking.remove(position));
+        }
+
+        // Pair down the ranking without rematches if at all possible.
+        let pairs = pair_ranking(&ranking, &met)
+            .unwrap_or_else(|| ranking.chunks(2).map(|pair| (pair[0], pair[1])).collect());
+
+        // Whoever has had White less often gets it.
+        let whites = |player: usize| {
+            earlier.iter().filter(|game| game.white == player).count() as i64
+                - earlier.iter().filter(|game| game.black == player).count() as i64
+        };
+        let pairings = pairs
+            .into_iter()
+            .map(|(player, opponent)| {
+                if whites(player) <= whites(opponent) {
+                    (player, opponent)
+                } else {
+                    (opponent, player)
+                }
+            })
+            .collect();
+        (pairings, bye)
+    }
+
+    // Points before the given round. A Swiss bye is worth winning every game
+    // of a pairing.
+    fn points(&self, round: usize, rounds: &[Round]) -> Vec<f64> {
+        let mut points = vec![0.0; self.names.len()];
+        for game in self.games.iter().filter(|game| game.round < round) {
+            let white = white_points(game.result);
+            points[game.white] += white;
+            points[game.black] += 1.0 - white;
+        }
+
+        for (_, bye) in &rounds[..round] {
+            if let (Format::Swiss, Some(player)) = (self.options.format, *bye) {
+                points[player] += self.options.games_per_pairing as f64;
+            }
+        }
+        points
+    }
+
+    // The games of a round, numbered within it, colours alternating within
+    // each pairing. Openings follow on from the previous rounds.
+    pub fn schedule(&self, round: usize) -> Vec<ScheduledGame> {
+        let mut rounds = self.rounds(round + 1);
+        let (pairings, _) = rounds.pop().unwrap();
+        let pairings_before: usize = rounds.iter().map(|(pairings, _)| pairings.len()).sum();
+
+        pairings
+            .iter()
+            .enumerate()
+            .flat_map(|(index, &(first, second))| {
+                (0..self.options.games_per_pairing).map(move |game| {
+                    let (white, black) = if game % 2 == 0 {
+                        (first, second)
+                    } else {
+                        (second, first)
+                    };
+                    ScheduledGame {
+                        white,
+                        black,
+                        opening: pairings_before + index,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        let total: usize = self
+            .rounds(self.round_count())
+            .iter()
+            .map(|(pairings, _)| pairings.len() * self.options.games_per_pairing)
+            .sum();
+        self.games.len() >= total
+    }
+
+    // Each player's score against the whole field.
+    pub fn scores(&self) -> Vec<Score> {
+        let mut scores = vec![Score::default(); self.names.len()];
+        for game in &self.games {
+            let white = white_points(game.result);
+            scores[game.white].add(white);
+            scores[game.black].add(1.0 - white);
+        }
+        scores
+    }
+
+    pub fn crosstable(&self) -> String {
+        let rounds = self.rounds(self.round_count());
+        let points = self.points(rounds.len(), &rounds);
+        let scores = self.scores();
+        let mut ranking: Vec<_> = (0..self.names.len()).collect();
+        ranking.sort_by(|&a, &b| points[b].total_cmp(&points[a]).then(a.cmp(&b)));
+
+        let width = self.names.iter().map(String::len).max().unwrap_or(0).max(6);
+        let mut table = format!(
+            "{:>3}  {:<width$}  {:>6}  {:>5}  {:>14}",
+            "#", "Player", "Points", "Games", "Elo"
+        );
+        for rank in 1..=ranking.len() {
+            table.push_str(&format!("  {:>5}", rank));
+        }
+        table.push('\n');
+
+        for (rank, &player) in ranking.iter().enumerate() {
+            let elo = match scores[player].elo() {
+                Some((elo, margin)) if elo.is_finite() => format!("{:+.0} +/- {:.0}", elo, margin),
+                _ => "-".to_string(),
+            };
+            table.push_str(&format!(
+                "{:>3}  {:<width$}  {:>6.1}  {:>5}  {:>14}",
+                rank + 1,
+                self.names[player],
+                points[player],
+                scores[player].games(),
+                elo
+            ));
+
+            // Points against each opponent, in ranking order.
+            for &opponent in &ranking {
+                if opponent == player {
+                    table.push_str(&format!("  {:>5}", "*"));
+                    continue;
+                }
+
+                let mut score = None;
+                for game in &self.games {
+                    let white = white_points(game.result);
+                    if (game.white, game.black) == (player, opponent) {
+                        *score.get_or_insert(0.0) += white;
+                    } else if (game.white, game.black) == (opponent, player) {
+                        *score.get_or_insert(0.0) += 1.0 - white;
+                    }
+                }
+                match score {
+                    Some(score) => table.push_str(&format!("  {:>5.1}", score)),
+                    None => table.push_str(&format!("  {:>5}", "")),
+                }
+            }
+            table = table.trim_end().to_string();
+            table.push('\n');
+        }
+        table
+    }
+
+    // A line per setting, player and finished game.
+    pub fn to_state(&self) -> String {
+        let mut state = format!(
+            "format {}\nrounds {}\ngames {}\n",
+            self.options.format.name(),
+            self.options.rounds,
+            self.options.games_per_pairing
+        );
+        for name in &self.names {
+            state.push_str(&format!("player {}\n", name));
+        }
+        for game in &self.games {
+            state.push_str(&format!(
+                "result {} {} {} {} {}\n",
+                game.round,
+                game.game,
+                game.white,
+                game.black,
+                game.result.token()
+            ));
+        }
+        state
+    }
+
+    pub fn from_state(text: &str) -> Option<Self> {
+        let mut tournament = Self::new(Vec::new(), TournamentOptions::default());
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line.split_once(' ')?;
+            match key {
+                "format" => tournament.options.format = Format::from_name(value)?,
+                "rounds" => tournament.options.rounds = value.parse().ok()?,
+                "games" => tournament.options.games_per_pairing = value.parse().ok()?,
+                "player" => tournament.names.push(value.to_string()),
+                "result" => {
+                    let fields: Vec<_> = value.split_whitespace().collect();
+                    let [round, game, white, black, result] = fields[..] else {
+                        return None;
+                    };
+                    let result = GameResult::from_token(result)?;
+                    tournament.games.push(PlayedGame {
+                        round: round.parse().ok()?,
+                        game: game.parse().ok()?,
+                        white: white.parse().ok()?,
+                        black: black.parse().ok()?,
+                        result,
+                    });
+                }
+                _ => return None,
+            }
+        }
+
+        (tournament.names.len() >= 2).then_some(tournament)
+    }
+}
+
+fn usage() {
+    println!(
+        "Usage: tournament <engine>... [--format roundrobin|gauntlet|swiss] [--rounds N] \
+         [--games N] [--concurrency N] [--tc SECONDS+INC] [--openings FILE] [--pgn FILE] \
+         [--state FILE] [--option N NAME=VALUE]"
+    );
+}
+
+pub fn run(args: &[String]) {
+    let mut options = TournamentOptions::default();
+    let mut engines = Vec::new();
+    let mut options_by_engine = Vec::new();
+    let mut concurrency = 1;
+    let mut time_control = TimeControl::parse("10+0.1").unwrap();
+    let mut openings_path = None;
+    let mut pgn_path = None;
+    let mut state_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(String::as_str);
+
+        let valid = match arg.as_str() {
+            "--format" => value().and_then(Format::from_name).map(|value| {
+                options.format = value;
+            }),
+            "--rounds" => value().and_then(|value| value.parse().ok()).map(|value| {
+                options.rounds = value;
+            }),
+            "--games" => value()
+                .and_then(|value| value.parse().ok())
+                .filter(|&value| value > 0)
+                .map(|value| {
+                    options.games_per_pairing = value;
+                }),
+            "--concurrency" => value().and_then(|value| value.parse().ok()).map(|value| {
+                concurrency = value;
+            }),
+            "--tc" => value().and_then(TimeControl::parse).map(|value| {
+                time_control = value;
+            }),
+            "--openings" => value().map(|value| {
+                openings_path = Some(value.to_string());
+            }),
+            "--pgn" => value().map(|value| {
+                pgn_path = Some(value.to_string());
+            }),
+            "--state" => value().map(|value| {
+                state_path = Some(value.to_string());
+            }),
+            "--option" => {
+                let engine = value().and_then(|value| value.parse::<usize>().ok());
+                let option = value().and_then(|value| value.split_once('='));
+                engine.zip(option).map(|(engine, (name, value))| {
+                    options_by_engine.push((engine, name.to_string(), value.to_string()));
+                })
+            }
+            _ if !arg.starts_with("--") => {
+                engines.push(EngineConfig {
+                    command: arg.split_whitespace().map(str::to_string).collect(),
+                    options: Vec::new(),
+                });
+                Some(())
+            }
+            _ => None,
+        };
+
+        if valid.is_none() {
+            println!("Invalid argument {}", arg);
+            return usage();
+        }
+    }
+
+    if engines.len() < 2 {
+        return usage();
+    }
+    for (engine, name, value) in options_by_engine {
+        match engines.get_mut(engine.wrapping_sub(1)) {
+            Some(config) => config.options.push((name, value)),
+            None => return println!("No engine {} for option {}", engine, name),
+        }
+    }
+
+    let openings = match &openings_path {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(text) => parse_openings(&text),
+            Err(error) => return println!("Cannot read {}: {}", path, error),
+        },
+        None => Vec::new(),
+    };
+
+    let names = match player_names(&engines) {
+        Ok(names) => names,
+        Err(error) => return println!("Cannot start the engines: {}", error),
+    };
+
+    // A saved state for the same players and settings picks up where it
+    // stopped. One that can't be read is left alone rather than overwritten.
+    let mut tournament = Tournament::new(names, options);
+    if let Some(path) = &state_path {
+        match fs::read_to_string(path).map(|text| Tournament::from_state(&text)) {
+            Ok(Some(saved))
+                if saved.names == tournament.names && saved.options == tournament.options =>
+            {
+                println!("Resuming after {} games", saved.games.len());
+                tournament = saved;
+            }
+            Ok(Some(_)) => return println!("{} holds a different tournament", path),
+            Ok(None) => return println!("{} is not a valid tournament state", path),
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => return println!("Cannot read {}: {}", path, error),
+        }
+    }
+
+    if let Err(error) = play_tournament(
+        &mut tournament,
+        &engines,
+        &openings,
+        time_control,
+        concurrency,
+        pgn_path.as_deref(),
+        state_path.as_deref(),
+    ) {
+        println!("Tournament stopped: {}", error);
+    }
+    print!("{}", tournament.crosstable());
+}
+
+fn play_tournament(
+    tournament: &mut Tournament,
+    engines: &[EngineConfig],
+    openings: &[Opening],
+    time_control: TimeControl,
+    concurrency: usize,
+    pgn_path: Option<&str>,
+    state_path: Option<&str>,
+) -> io::Result<()> {
+    let mut pgn = match pgn_path {
+        Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+        None => None,
+    };
+
+    for round in 0..tournament.round_count() {
+        let schedule = tournament.schedule(round);
+        let (indices, games): (Vec<_>, Vec<_>) = schedule
+            .into_iter()
+            .enumerate()
+            .filter(|(game, _)| {
+                !tournament
+                    .games
+                    .iter()
+                    .any(|played| (played.round, played.game) == (round, *game))
+            })
+            .unzip();
+        if games.is_empty() {
+            continue;
+        }
+
+        println!("Round {}", round + 1);
+        let names = tournament.names.clone();
+        let mut error = None;
+        play_games(
+            engines,
+            &names,
+            openings,
+            &games,
+            time_control,
+            concurrency,
+            |index, record| {
+                let game = games[index];
+                println!(
+                    "{} - {} {} ({})",
+                    record.white,
+                    record.black,
+                    record.result.token(),
+                    record.termination
+                );
+
+                tournament.games.push(PlayedGame {
+                    round,
+                    game: indices[index],
+                    white: game.white,
+                    black: game.black,
+                    result: record.result,
+                });
+
+                let saved = pgn
+                    .as_mut()
+                    .map_or(Ok(()), |pgn| {
+                        pgn.write_all(record.to_pgn(round + 1).as_bytes())
+                    })
+                    .and_then(|()| state_path.map_or(Ok(()), |path| save(path, tournament)));
+                if let Err(saved) = saved {
+                    error = Some(saved);
+                }
+                error.is_none()
+            },
+        )?;
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+        if round + 1 < tournament.round_count() {
+            print!("{}", tournament.crosstable());
+        }
+    }
+
+    Ok(())
+}
+
+// Written to a temporary file first so that an interruption can't leave a
+// half-written state behind.
+fn save(path: &str, tournament: &Tournament) -> io::Result<()> {
+    let temporary = format!("{}.tmp", path);
+    fs::write(&temporary, tournament.to_state())?;
+    fs::rename(&temporary, Path::new(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tournament(players: usize, format: Format, rounds: usize) -> Tournament {
+        let names = (0..players)
+            .map(|player| format!("Engine {}", player))
+            .collect();
+        Tournament::new(
+            names,
+            TournamentOptions {
+                format,
+                rounds,
+                games_per_pairing: 2,
+            },
+        )
+    }
+
+    // Plays every scheduled game of the round, the lower index winning.
+    fn play_round(tournament: &mut Tournament, round: usize) {
+        for (game, scheduled) in tournament.schedule(round).into_iter().enumerate() {
+            let result = if scheduled.white < scheduled.black {
+                GameResult::WhiteWins
+            } else {
+                GameResult::BlackWins
+            };
+            tournament.games.push(PlayedGame {
+                round,
+                game,
+                white: scheduled.white,
+                black: scheduled.black,
+                result,
+            });
+        }
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once() {
+        for players in [2, 5, 6] {
+            let tournament = tournament(players, Format::RoundRobin, 1);
+            let mut pairs = Vec::new();
+            for round in 0..tournament.round_count() {
+                let (pairings, bye) = tournament.pairings(round);
+                assert_eq!(bye.is_some(), players % 2 == 1);
+                pairs.extend(pairings.iter().map(|&(a, b)| (a.min(b), a.max(b))));
+            }
+
+            pairs.sort();
+            pairs.dedup();
+            assert_eq!(pairs.len(), players * (players - 1) / 2);
+        }
+    }
+
+    #[test]
+    fn gauntlet_plays_the_first_engine() {
+        let mut tournament = tournament(4, Format::Gauntlet, 2);
+        assert_eq!(tournament.round_count(), 2);
+        assert_eq!(tournament.schedule(0).len(), 6);
+
+        play_round(&mut tournament, 0);
+        play_round(&mut tournament, 1);
+        assert!(tournament.is_finished());
+        assert_eq!(tournament.scores()[0].wins, 12);
+        assert!(tournament
+            .games
+            .iter()
+            .all(|game| game.white == 0 || game.black == 0));
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let mut tournament = tournament(5, Format::Swiss, 3);
+
+        let mut byes = Vec::new();
+        for round in 0..3 {
+            let (pairings, bye) = tournament.pairings(round);
+            assert_eq!(pairings.len(), 2);
+            byes.push(bye.unwrap());
+
+            for &(a, b) in &pairings {
+                assert!(!tournament.games.iter().any(|game| {
+                    (game.white, game.black) == (a, b) || (game.white, game.black) == (b, a)
+                }));
+            }
+            play_round(&mut tournament, round);
+        }
+
+        byes.sort();
+        byes.dedup();
+        assert_eq!(byes.len(), 3);
+        assert!(tournament.is_finished());
+    }
+
+    #[test]
+    fn state_round_trip_and_crosstable() {
+        let mut tournament = tournament(3, Format::RoundRobin, 1);
+        play_round(&mut tournament, 0);
+        play_round(&mut tournament, 1);
+        tournament.games[0].result = GameResult::Draw;
+        tournament.games[1].result = GameResult::Unknown;
+
+        let state = tournament.to_state();
+        assert_eq!(Tournament::from_state(&state), Some(tournament.clone()));
+        assert_eq!(Tournament::from_state("format swiss\nplayer A\n"), None);
+        assert!(!tournament.is_finished());
+
+        let crosstable = tournament.crosstable();
+        let lines: Vec<_> = crosstable.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("Player") && lines[0].contains("Elo"));
+        assert!(lines[1].contains("Engine 0"));
+    }
+}
@@ -0,0 +1,232 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    movegen::{Move, MoveType},
+    piece::Piece,
+};
+
+const NO_PIECE: u64 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TtEntry {
+    pub key: u64,
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+fn encode_square((rank, file): (u8, u8)) -> u64 {
+    (rank * 8 + file) as u64
+}
+
+fn decode_square(bits: u64) -> (u8, u8) {
+    let square = (bits & 63) as u8;
+    (square / 8, square % 8)
+}
+
+fn encode_piece(piece: Option<Piece>) -> u64 {
+    piece.map_or(NO_PIECE, |piece| piece.index() as u64)
+}
+
+fn decode_piece(bits: u64) -> Option<Piece> {
+    Piece::from_index((bits & 15) as usize)
+}
+
+// Packs a move into 27 bits: squares, moving piece, move type and the pieces
+// the move type carries.
+fn encode_move(chess_move: &Move) -> u64 {
+    let (tag, first, second) = match chess_move.move_type {
+        MoveType::Normal => (0, None, None),
+        MoveType::Capture(capture) => (1, Some(capture), None),
+        MoveType::EnPassant => (2, None, None),
+        MoveType::Promotion(promotion, capture) => (3, Some(promotion), capture),
+        MoveType::Castling => (4, None, None),
+        MoveType::Drop => (5, None, None),
+    };
+
+    encode_square(chess_move.from)
+        | encode_square(chess_move.to) << 6
+        | (chess_move.piece.index() as u64) << 12
+        | tag << 16
+        | encode_piece(first) << 19
+        | encode_piece(second) << 23
+}
+
+fn decode_move(bits: u64) -> Option<Move> {
+    let move_type = match (bits >> 16) & 7 {
+        0 => MoveType::Normal,
+        1 => MoveType::Capture(decode_piece(bits >> 19)?),
+        2 => MoveType::EnPassant,
+        3 => MoveType::Promotion(decode_piece(bits >> 19)?, decode_piece(bits >> 23)),
+        4 => MoveType::Castling,
+        5 => MoveType::Drop,
+        _ => return None,
+    };
+
+    Some(Move {
+        from: decode_square(bits),
+        to: decode_square(bits >> 6),
+        piece: decode_piece(bits >> 12)?,
+        move_type,
+    })
+}
+
+// Layout: move in bits 0-26, move present flag in bit 27, score in 32-47,
+// depth in 48-55 and bound in 56-57, where a zero bound marks an empty slot.
+fn encode_entry(entry: &TtEntry) -> u64 {
+    let best_move = entry
+        .best_move
+        .map_or(0, |chess_move| encode_move(&chess_move) | 1 << 27);
+    let bound = match entry.bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+
+    best_move
+        | (entry.score as i16 as u16 as u64) << 32
+        | (entry.depth.min(255) as u64) << 48
+        | bound << 56
+}
+
+fn decode_entry(key: u64, data: u64) -> Option<TtEntry> {
+    let bound = match (data >> 56) & 3 {
+        1 => Bound::Exact,
+        2 => Bound::Lower,
+        3 => Bound::Upper,
+        _ => return None,
+    };
+    let best_move = if data & 1 << 27 != 0 {
+        decode_move(data)
+    } else {
+        None
+    };
+
+    Some(TtEntry {
+        key,
+        best_move,
+        score: (data >> 32) as u16 as i16 as i32,
+        depth: ((data >> 48) & 255) as u32,
+        bound,
+    })
+}
+
+// Shared between search threads without locking. Each slot stores the key
+// XORed with the data, so a slot torn by two racing writers fails the key
+// check on probe instead of returning a mixed entry.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb.max(1) * 1024 * 1024 / size_of::<[AtomicU64; 2]>()).max(1);
+
+        Self {
+            slots: (0..count)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.slots.len() as u128) >> 64) as usize
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        let [check, data] = &self.slots[self.index(key)];
+        let data = data.load(Ordering::Relaxed);
+
+        if check.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        decode_entry(key, data)
+    }
+
+    pub fn store(&self, entry: TtEntry) {
+        // Keep a deeper entry for the same position unless the new one is exact.
+        if let Some(existing) = self.probe(entry.key) {
+            if existing.depth > entry.depth && entry.bound != Bound::Exact {
+                return;
+            }
+        }
+
+        let [check, data] = &self.slots[self.index(entry.key)];
+        let value = encode_entry(&entry);
+        check.store(entry.key ^ value, Ordering::Relaxed);
+        data.store(value, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for [check, data] in &self.slots {
+            check.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::Board, movegen::legal_moves};
+
+    fn entry(key: u64, depth: u32, bound: Bound) -> TtEntry {
+        TtEntry {
+            key,
+            best_move: None,
+            score: depth as i32,
+            depth,
+            bound,
+        }
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+
+        tt.store(entry(42, 3, Bound::Exact));
+        assert_eq!(tt.probe(42), Some(entry(42, 3, Bound::Exact)));
+        assert_eq!(tt.probe(43), None);
+    }
+
+    #[test]
+    fn shallow_bound_does_not_replace_deeper_entry() {
+        let tt = TranspositionTable::new(1);
+
+        tt.store(entry(42, 6, Bound::Lower));
+        tt.store(entry(42, 2, Bound::Upper));
+        assert_eq!(tt.probe(42).unwrap().depth, 6);
+
+        tt.store(entry(42, 2, Bound::Exact));
+        assert_eq!(tt.probe(42).unwrap().depth, 2);
+    }
+
+    #[test]
+    fn entries_round_trip_moves_and_scores() {
+        let tt = TranspositionTable::new(1);
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+
+        for (score, chess_move) in (-31000..).step_by(997).zip(legal_moves(&mut board)) {
+            let stored = TtEntry {
+                key: board.hash,
+                best_move: Some(chess_move),
+                score,
+                depth: 9,
+                bound: Bound::Lower,
+            };
+
+            tt.clear();
+            tt.store(stored);
+            assert_eq!(tt.probe(board.hash), Some(stored));
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::{fmt::Write as _, fs};
+
+use crate::{
+    board::Board,
+    datagen::Sample,
+    endgame,
+    eval::{features, parameters, PARAMETER_COUNT, TABLE_NAMES},
+    pgn::GameResult,
+};
+
+// A position's game result for White: 1.0, 0.5 or 0.0. Reads the datagen
+// text format, and EPD lines labelled as `c9 "1-0";` or `[1.0]`.
+pub fn parse_labelled(line: &str) -> Option<(Board, f64)> {
+    if let Some(sample) = Sample::from_text(line) {
+        let result = match sample.result {
+            GameResult::WhiteWins => 1.0,
+            GameResult::Draw => 0.5,
+            GameResult::BlackWins => 0.0,
+            GameResult::Unknown => return None,
+        };
+        return Some((Board::from_fen(&sample.fen)?, result));
+    }
+
+    let fields: Vec<_> = line.split_whitespace().collect();
+    if fields.len() < 5 {
+        return None;
+    }
+
+    let labels = [
+        ("1-0", 1.0),
+        ("0-1", 0.0),
+        ("1/2-1/2", 0.5),
+        ("[1.0]", 1.0),
+        ("[0.0]", 0.0),
+        ("[0.5]", 0.5),
+    ];
+    let rest = fields[4..].join(" ");
+    let result = labels
+        .iter()
+        .find(|(label, _)| rest.contains(label))
+        .map(|(_, result)| *result)?;
+
+    // EPD has no move counters, but a full FEN may come first.
+    let fen = if fields.len() >= 6 && fields[4].parse::<u32>().is_ok() {
+        fields[..6].join(" ")
+    } else {
+        format!("{} 0 1", fields[..4].join(" "))
+    };
+    Some((Board::from_fen(&fen)?, result))
+}
+
+struct Entry {
+    features: Vec<(usize, f64)>,
+    result: f64,
+}
+
+// The chance of winning from an evaluation, with K fitted to the data.
+fn sigmoid(k: f64, score: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+// Texel tuning: the classic evaluation is linear in its parameters, so the
+// mean squared error between the sigmoid of the static evaluation and the
+// game result is minimised by gradient descent.
+pub struct Tuner {
+    entries: Vec<Entry>,
+    pub parameters: Vec<f64>,
+    pub k: f64,
+}
+
+impl Tuner {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            parameters: parameters().into_iter().map(f64::from).collect(),
+            k: 1.0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Positions with specialised endgame knowledge aren't scored by the
+    // parameters and are left out.
+    pub fn add(&mut self, board: &Board, result: f64) -> bool {
+        if endgame::probe(board).is_some() {
+            return false;
+        }
+
+        let features = features(board)
+            .into_iter()
+            .map(|(index, coefficient)| (index, coefficient as f64))
+            .collect();
+        self.entries.push(Entry { features, result });
+        true
+    }
+
+    fn score(&self, entry: &Entry) -> f64 {
+        entry
+            .features
+            .iter()
+            .map(|&(index, coefficient)| self.parameters[index] * coefficient)
+            .sum()
+    }
+
+    pub fn error(&self, k: f64) -> f64 {
+        let total: f64 = self
+            .entries
+            .iter()
+            .map(|entry| (entry.result - sigmoid(k, self.score(entry))).powi(2))
+            .sum();
+        total / self.entries.len().max(1) as f64
+    }
+
+    // Picks the K that fits the current parameters best, by narrowing a
+    // scan around the best value found so far.
+    pub fn fit_k(&mut self) -> f64 {
+        let (mut low, mut high) = (0.0, 4.0);
+
+        for _ in 0..6 {
+            let step = (high - low) / 20.0;
+            let best = (0..=20)
+                .map(|i| low + step * i as f64)
+                .min_by(|&a, &b| self.error(a).total_cmp(&self.error(b)))
+                .unwrap();
+            (low, high) = ((best - step).max(0.0), best + step);
+        }
+
+        self.k = (low + high) / 2.0;
+        self.k
+    }
+
+    fn gradient(&self) -> Vec<f64> {
+        let mut gradient = vec![0.0; PARAMETER_COUNT];
+        let scale = self.k * 10f64.ln() / 400.0;
+
+        for entry in &self.entries {
+            let predicted = sigmoid(self.k, self.score(entry));
+            let slope = -2.0 * (entry.result - predicted) * predicted * (1.0 - predicted) * scale;
+
+            for &(index, coefficient) in &entry.features {
+                gradient[index] += slope * coefficient;
+            }
+        }
+
+        let count = self.entries.len().max(1) as f64;
+        gradient.iter_mut().for_each(|value| *value /= count);
+        gradient
+    }
+
+    // Runs Adam over the whole data set, reporting the error after each
+    // epoch.
+    pub fn tune(
+        &mut self,
+        epochs: usize,
+        learning_rate: f64,
+        mut on_epoch: impl FnMut(usize, f64),
+    ) {
+        const BETA1: f64 = 0.9;
+        const BETA2: f64 = 0.999;
+        const EPSILON: f64 = 1e-8;
+
+        let mut momentum = vec![0.0; PARAMETER_COUNT];
+        let mut velocity = vec![0.0; PARAMETER_COUNT];
+
+        for epoch in 1..=epochs {
+            let gradient = self.gradient();
+
+            for index in 0..PARAMETER_COUNT {
+                momentum[index] = BETA1 * momentum[index] + (1.0 - BETA1) * gradient[index];
+                velocity[index] = BETA2 * velocity[index] + (1.0 - BETA2) * gradient[index].powi(2);
+
+                let momentum = momentum[index] / (1.0 - BETA1.powi(epoch as i32));
+                let velocity = velocity[index] / (1.0 - BETA2.powi(epoch as i32));
+                self.parameters[index] -= learning_rate * momentum / (velocity.sqrt() + EPSILON);
+            }
+
+            on_epoch(epoch, self.error(self.k));
+        }
+    }
+
+    // The tuned values as the constants in eval.rs.
+    pub fn to_rust_source(&self) -> String {
+        let values: Vec<_> = self
+            .parameters
+            .iter()
+            .map(|value| value.round() as i32)
+            .collect();
+        let (piece_values, tables) = values.split_at(6);
+
+        let mut source = String::new();
+        let piece_values: Vec<_> = piece_values.iter().map(i32::to_string).collect();
+        writeln!(
+            source,
+            "pub const PIECE_VALUES: [i32; 6] = [{}];",
+            piece_values.join(", ")
+        )
+        .unwrap();
+
+        for (name, table) in TABLE_NAMES.iter().zip(tables.chunks(64)) {
+            writeln!(source, "\n#[rustfmt::skip]\nconst {}: [i32; 64] = [", name).unwrap();
+            for row in table.chunks(8) {
+                let row: Vec<_> = row.iter().map(|value| format!("{:>3}", value)).collect();
+                writeln!(source, "    {},", row.join(",")).unwrap();
+            }
+            writeln!(source, "];").unwrap();
+        }
+
+        source
+    }
+}
+
+fn usage() {
+    println!("Usage: tune <positions>... [--epochs N] [--learning-rate X] [--output FILE]");
+}
+
+pub fn run(args: &[String]) {
+    let mut epochs = 1000;
+    let mut learning_rate = 1.0;
+    let mut output = None;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let valid = match arg.as_str() {
+            "--epochs" => args
+                .next()
+                .and_then(|value| value.parse().ok())
+                .map(|value| {
+                    epochs = value;
+                }),
+            "--learning-rate" => args
+                .next()
+                .and_then(|value| value.parse().ok())
+                .map(|value| {
+                    learning_rate = value;
+                }),
+            "--output" => args.next().map(|value| {
+                output = Some(value);
+            }),
+            _ => {
+                paths.push(arg);
+                Some(())
+            }
+        };
+
+        if valid.is_none() {
+            println!("Invalid value for {}", arg);
+            return usage();
+        }
+    }
+
+    if paths.is_empty() {
+        return usage();
+    }
+
+    let mut tuner = Tuner::new();
+    let mut skipped = 0;
+    for path in paths {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) => {
+                println!("Cannot read {}: {}", path, error);
+                continue;
+            }
+        };
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match parse_labelled(line) {
+                Some((board, result)) if tuner.add(&board, result) => {}
+                _ => skipped += 1,
+            }
+        }
+    }
+
+    if tuner.is_empty() {
+        return println!("No labelled positions found");
+    }
+
+    let k = tuner.fit_k();
+    println!(
+        "Loaded {} positions ({} skipped), K = {:.4}, error {:.6}",
+        tuner.len(),
+        skipped,
+        k,
+        tuner.error(k)
+    );
+
+    tuner.tune(epochs, learning_rate, |epoch, error| {
+        if epoch % 100 == 0 || epoch == epochs {
+            println!("Epoch {}: error {:.6}", epoch, error);
+        }
+    });
+
+    let source = tuner.to_rust_source();
+    match output {
+        Some(path) => match fs::write(path, source) {
+            Ok(()) => println!("Wrote tuned parameters to {}", path),
+            Err(error) => println!("Cannot write {}: {}", path, error),
+        },
+        None => print!("{}", source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labelled_formats() {
+        let (_, result) = parse_labelled("4k3/8/8/8/8/8/8/3QK3 w - - 0 1 | 950 | 1.0").unwrap();
+        assert_eq!(result, 1.0);
+
+        let (board, result) = parse_labelled("4k3/8/8/8/8/8/8/3QK3 b - - c9 \"0-1\";").unwrap();
+        assert_eq!(result, 0.0);
+        assert_eq!(board.halfmove_clock, 0);
+
+        let (board, result) = parse_labelled("4k3/8/8/8/8/8/8/3QK3 w - - 12 40 [0.5]").unwrap();
+        assert_eq!(result, 0.5);
+        assert_eq!(board.halfmove_clock, 12);
+
+        assert!(parse_labelled("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").is_none());
+    }
+
+    #[test]
+    fn tuning_reduces_the_error() {
+        let mut tuner = Tuner::new();
+
+        // An extra knight always wins and an extra pawn on the sixth rank
+        // mostly does, so both should gain value.
+        let positions = [
+            ("4k3/pp6/8/8/8/8/PP6/1N2K3 w - - 0 1", 1.0),
+            ("4k3/pp6/8/8/8/8/PP4N1/4K3 b - - 0 1", 1.0),
+            ("1n2k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1", 0.0),
+            ("4k3/1p6/P7/8/8/8/8/4K3 w - - 0 1", 1.0),
+            ("4k3/8/8/8/8/p7/1P6/4K3 b - - 0 1", 0.0),
+            ("4k3/pp6/8/8/8/8/PP6/4K3 w - - 0 1", 0.5),
+        ];
+        for (fen, result) in positions {
+            assert!(tuner.add(&Board::from_fen(fen).unwrap(), result));
+        }
+        assert!(!tuner.add(
+            &Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap(),
+            1.0
+        ));
+
+        let k = tuner.fit_k();
+        let before = tuner.error(k);
+        let knight = tuner.parameters[1];
+
+        tuner.tune(200, 2.0, |_, _| {});
+        assert!(tuner.error(k) < before);
+        assert!(tuner.parameters[1] > knight);
+
+        let source = tuner.to_rust_source();
+        assert!(source.starts_with("pub const PIECE_VALUES: [i32; 6] = ["));
+        assert!(source.contains("#[rustfmt::skip]\nconst KING_TABLE: [i32; 64] = [\n"));
+    }
+}
@@ -0,0 +1,615 @@
+use std::{
+    io::BufRead,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    board::{Board, STARTING_FEN_STRING},
+    book::{Book, BookSelection},
+    movegen::{format_uci, parse_uci_move, Move},
+    nnue::Network,
+    search::{SearchInfo, SearchLimits, SearchOptions, Searcher, MATE, MAX_PLY},
+    tt::TranspositionTable,
+    variant::{self, Standard, Variant, VARIANTS},
+};
+
+const ENGINE_NAME: &str = "chessrs";
+const ENGINE_AUTHOR: &str = "ttkien2125";
+
+// Check options that switch individual search features on and off.
+const SEARCH_TOGGLES: [&str; 8] = [
+    "MoveOrdering",
+    "NullMove",
+    "LMR",
+    "Futility",
+    "ReverseFutility",
+    "AspirationWindows",
+    "CheckExtensions",
+    "PVS",
+];
+
+fn search_toggle<'a>(options: &'a mut SearchOptions, name: &str) -> Option<&'a mut bool> {
+    match name {
+        "MoveOrdering" => Some(&mut options.move_ordering),
+        "NullMove" => Some(&mut options.null_move),
+        "LMR" => Some(&mut options.late_move_reductions),
+        "Futility" => Some(&mut options.futility),
+        "ReverseFutility" => Some(&mut options.reverse_futility),
+        "AspirationWindows" => Some(&mut options.aspiration_windows),
+        "CheckExtensions" => Some(&mut options.check_extensions),
+        "PVS" => Some(&mut options.pvs),
+        _ => None,
+    }
+}
+
+// Positions are played under the given variant, unless it's standard chess
+// and the FEN's pocket or check counts call for another.
+pub fn parse_position(args: &[&str], variant: &'static dyn Variant) -> Option<Board> {
+    let board = |fen: &str| {
+        if variant.is_standard() {
+            Board::from_fen(fen)
+        } else {
+            Board::from_variant_fen(fen, variant)
+        }
+    };
+    let (mut board, rest) = match args.first() {
+        Some(&"startpos") => (board(variant.start_fen())?, &args[1..]),
+        Some(&"fen") => {
+            let fen_end = args
+                .iter()
+                .position(|arg| *arg == "moves")
+                .unwrap_or(args.len());
+            (board(&args[1..fen_end].join(" "))?, &args[fen_end..])
+        }
+        _ => return None,
+    };
+    if let Some((&"moves", moves)) = rest.split_first() {
+        for uci in moves {
+            let chess_move = parse_uci_move(&mut board, uci)?;
+            board.make_move(&chess_move);
+        }
+    }
+
+    Some(board)
+}
+
+pub fn parse_go(args: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().and_then(|value| value.parse::<u64>().ok());
+
+        match *arg {
+            "depth" => limits.depth = value().map(|depth| depth as u32),
+            "nodes" => limits.nodes = value(),
+            "movetime" => limits.movetime = value(),
+            "wtime" => limits.wtime = value(),
+            "btime" => limits.btime = value(),
+            "winc" => limits.winc = value(),
+            "binc" => limits.binc = value(),
+            "movestogo" => limits.movestogo = value().map(|moves| moves as u32),
+            "infinite" => limits.infinite = true,
+            _ => {}
+        }
+    }
+
+    limits
+}
+
+// Splits "setoption name <name> value <value>" into its name and value.
+pub fn parse_setoption(args: &[&str]) -> Option<(String, String)> {
+    let name_start = args.iter().position(|arg| *arg == "name")? + 1;
+    let value_start = args.iter().position(|arg| *arg == "value");
+
+    let name = args[name_start..value_start.unwrap_or(args.len())].join(" ");
+    let value = value_start
+        .map(|start| args[start + 1..].join(" "))
+        .unwrap_or_default();
+
+    Some((name, value))
+}
+
+pub fn format_score(score: i32) -> String {
+    if score.abs() >= MATE - MAX_PLY as i32 {
+        let plies = MATE - score.abs();
+        let moves = (plies + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+pub fn format_info(info: &SearchInfo, board: &Board, chess960: bool) -> String {
+    let millis = info.time.as_millis().max(1) as u64;
+    let pv: Vec<_> = info
+        .pv
+        .iter()
+        .map(|m| format_uci(board, m, chess960))
+        .collect();
+
+    format!(
+        "info depth {} seldepth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+        info.depth,
+        info.seldepth,
+        info.multipv,
+        format_score(info.score),
+        info.nodes,
+        info.nodes * 1000 / millis,
+        millis,
+        pv.join(" ")
+    )
+}
+
+struct Uci {
+    board: Board,
+    options: SearchOptions,
+    tt: Arc<TranspositionTable>,
+    book: Option<Book>,
+    book_selection: BookSelection,
+    network: Option<Arc<Network>>,
+    use_nnue: bool,
+    chess960: bool,
+    variant: &'static dyn Variant,
+    stop: Arc<AtomicBool>,
+    search_thread: Option<JoinHandle<()>>,
+}
+
+impl Uci {
+    fn new() -> Self {
+        Self {
+            board: Board::from_fen(STARTING_FEN_STRING).unwrap(),
+            options: SearchOptions::default(),
+            tt: Arc::new(TranspositionTable::new(SearchOptions::default().hash)),
+            book: None,
+            book_selection: BookSelection::Weighted,
+            network: None,
+            use_nnue: true,
+            chess960: false,
+            variant: &Standard,
+            stop: Arc::new(AtomicBool::new(false)),
+            search_thread: None,
+        }
+    }
+
+    // Books only cover standard chess.
+    fn book_move(&mut self) -> Option<Move> {
+        if !self.board.variant.is_standard() {
+            return None;
+        }
+
+        let random = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64);
+
+        self.book
+            .as_ref()?
+            .select(&mut self.board, self.book_selection, random)
+    }
+
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(search_thread) = self.search_thread.take() {
+            search_thread.join().unwrap();
+        }
+    }
+
+    fn go(&mut self, limits: SearchLimits) {
+        self.stop_search();
+
+        if !limits.infinite {
+            if let Some(book_move) = self.book_move() {
+                println!(
+                    "bestmove {}",
+                    format_uci(&self.board, &book_move, self.chess960)
+                );
+                return;
+            }
+        }
+
+        self.stop = Arc::new(AtomicBool::new(false));
+        let stop = self.stop.clone();
+        let root = self.board.clone();
+        let chess960 = self.chess960;
+        let mut board = self.board.clone();
+        board.set_network(self.network.clone().filter(|_| self.use_nnue));
+        let options = self.options;
+        let tt = self.tt.clone();
+
+        self.search_thread = Some(thread::spawn(move || {
+            let mut searcher = Searcher::with_tt(board, limits, options, stop.clone(), tt);
+            let best_move =
+                searcher.search(|info| println!("{}", format_info(info, &root, chess960)));
+
+            // In infinite mode the best move may only be sent after "stop".
+            while limits.infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            match best_move {
+                Some(best_move) => {
+                    println!("bestmove {}", format_uci(&root, &best_move, chess960))
+                }
+                None => println!("bestmove 0000"),
+            }
+        }));
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) {
+        if let Some(flag) = search_toggle(&mut self.options, name) {
+            match value.parse() {
+                Ok(enabled) => *flag = enabled,
+                Err(_) => println!("info string Invalid value {} for {}", value, name),
+            }
+            return;
+        }
+
+        match name {
+            "Move Overhead" => match value.parse() {
+                Ok(move_overhead) => self.options.move_overhead = move_overhead,
+                Err(_) => println!("info string Invalid value {} for {}", value, name),
+            },
+            "Hash" => match value.parse() {
+                Ok(hash) => {
+                    self.stop_search();
+                    self.options.hash = hash;
+                    self.tt = Arc::new(TranspositionTable::new(hash));
+                }
+                Err(_) => println!("info string Invalid value {} for {}", value, name),
+            },
+            "MultiPV" => match value.parse::<usize>() {
+                Ok(multipv) if multipv > 0 => self.options.multipv = multipv,
+                _ => println!("info string Invalid value {} for {}", value, name),
+            },
+            "BookFile" => {
+                if value.is_empty() || value == "<empty>" {
+                    self.book = None;
+                    return;
+                }
+
+                match Book::open(value) {
+                    Ok(book) => self.book = Some(book),
+                    Err(error) => println!("info string Cannot open book {}: {}", value, error),
+                }
+            }
+            "EvalFile" => {
+                if value.is_empty() || value == "<empty>" {
+                    self.network = None;
+                    return;
+                }
+
+                match Network::open(value) {
+                    Ok(network) => {
+                        println!(
+                            "info string Loaded network {} with {} hidden neurons",
+                            value,
+                            network.hidden()
+                        );
+                        self.network = Some(Arc::new(network));
+                    }
+                    Err(error) => println!("info string Cannot open {}: {}", value, error),
+                }
+            }
+            "UseNNUE" => match value.parse() {
+                Ok(use_nnue) => self.use_nnue = use_nnue,
+                Err(_) => println!("info string Invalid value {} for {}", value, name),
+            },
+            "UCI_Variant" => match variant::from_name(value) {
+                Some(variant) => self.variant = variant,
+                None => println!("info string Invalid value {} for {}", value, name),
+            },
+            "UCI_Chess960" => match value.parse() {
+                Ok(chess960) => self.chess960 = chess960,
+                Err(_) => println!("info string Invalid value {} for {}", value, name),
+            },
+            "BookSelection" => match value {
+                "Best" => self.book_selection = BookSelection::Best,
+                "Weighted" => self.book_selection = BookSelection::Weighted,
+                _ => println!("info string Invalid value {} for {}", value, name),
+            },
+            "Threads" => match value.parse::<usize>() {
+                Ok(threads) if threads > 0 => self.options.threads = threads,
+                _ => println!("info string Invalid value {} for {}", value, name),
+            },
+            _ => println!("info string Unknown option {}", name),
+        }
+    }
+
+    fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        let Some((command, args)) = tokens.split_first() else {
+            return true;
+        };
+
+        match *command {
+            "uci" => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Move Overhead type spin default {} min 0 max 5000",
+                    SearchOptions::default().move_overhead
+                );
+                println!(
+                    "option name Hash type spin default {} min 1 max 4096",
+                    SearchOptions::default().hash
+                );
+                println!(
+                    "option name Threads type spin default {} min 1 max 256",
+                    SearchOptions::default().threads
+                );
+                println!(
+                    "option name MultiPV type spin default {} min 1 max 256",
+                    SearchOptions::default().multipv
+                );
+                println!("option name BookFile type string default <empty>");
+                println!("option name EvalFile type string default <empty>");
+                println!("option name UseNNUE type check default true");
+                println!("option name UCI_Chess960 type check default false");
+                let variants: Vec<_> = VARIANTS.iter().map(|variant| variant.name()).collect();
+                println!(
+                    "option name UCI_Variant type combo default chess var {}",
+                    variants.join(" var ")
+                );
+                println!(
+                    "option name BookSelection type combo default Weighted var Weighted var Best"
+                );
+                for name in SEARCH_TOGGLES {
+                    let mut defaults = SearchOptions::default();
+                    println!(
+                        "option name {} type check default {}",
+                        name,
+                        search_toggle(&mut defaults, name).unwrap()
+                    );
+                }
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.board =
+                    Board::from_variant_fen(self.variant.start_fen(), self.variant).unwrap();
+                self.tt.clear();
+            }
+            "position" => match parse_position(args, self.variant) {
+                Some(board) => self.board = board,
+                None => println!("info string Invalid position"),
+            },
+            "go" => self.go(parse_go(args)),
+            "stop" => self.stop_search(),
+            "setoption" => match parse_setoption(args) {
+                Some((name, value)) => self.set_option(&name, &value),
+                None => println!("info string Invalid option"),
+            },
+            "quit" => {
+                self.stop_search();
+                return false;
+            }
+            _ => println!("info string Unknown command {}", command),
+        }
+
+        true
+    }
+}
+
+pub fn run() {
+    let mut uci = Uci::new();
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        if !uci.handle(&line) {
+            return;
+        }
+    }
+
+    uci.stop_search();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        book::BookEntry,
+        nnue::tests::test_network,
+        polyglot::{encode_move, polyglot_key},
+    };
+
+    #[test]
+    fn position_with_moves() {
+        let board =
+            parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"], &Standard).unwrap();
+
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+    }
+
+    #[test]
+    fn position_from_fen() {
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        let args: Vec<_> = format!("fen {} moves e2e4", fen)
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let args: Vec<_> = args.iter().map(String::as_str).collect();
+
+        let board = parse_position(&args, &Standard).unwrap();
+        assert_eq!(board.to_fen(), "4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1");
+    }
+
+    #[test]
+    fn position_with_illegal_move() {
+        assert!(parse_position(&["startpos", "moves", "e2e5"], &Standard).is_none());
+
+        let crazyhouse = variant::from_name("crazyhouse").unwrap();
+        let moves = ["startpos", "moves", "e2e4", "d7d5", "e4d5", "d8d5"];
+        assert!(parse_position(&moves, crazyhouse).is_some());
+        assert!(parse_position(&[&moves[..], &["N@f33"]].concat(), crazyhouse).is_none());
+    }
+
+    #[test]
+    fn position_with_malformed_fen() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - e33 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - é3 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - e 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+            "P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4K3",
+        ] {
+            let args: Vec<_> = format!("fen {}", fen)
+                .split_whitespace()
+                .map(String::from)
+                .collect();
+            let args: Vec<_> = args.iter().map(String::as_str).collect();
+            assert!(parse_position(&args, &Standard).is_none(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn go_limits() {
+        let limits = parse_go(&[
+            "wtime",
+            "1000",
+            "btime",
+            "2000",
+            "winc",
+            "10",
+            "binc",
+            "20",
+            "movestogo",
+            "5",
+        ]);
+
+        assert_eq!(limits.wtime, Some(1000));
+        assert_eq!(limits.btime, Some(2000));
+        assert_eq!(limits.winc, Some(10));
+        assert_eq!(limits.binc, Some(20));
+        assert_eq!(limits.movestogo, Some(5));
+        assert!(!limits.infinite);
+
+        assert_eq!(parse_go(&["depth", "7"]).depth, Some(7));
+        assert!(parse_go(&["infinite"]).infinite);
+    }
+
+    #[test]
+    fn setoption_name_and_value() {
+        assert_eq!(
+            parse_setoption(&["name", "Move", "Overhead", "value", "30"]),
+            Some(("Move Overhead".to_string(), "30".to_string()))
+        );
+    }
+
+    #[test]
+    fn search_toggles() {
+        let mut uci = Uci::new();
+
+        uci.set_option("NullMove", "false");
+        uci.set_option("PVS", "false");
+        assert!(!uci.options.null_move);
+        assert!(!uci.options.pvs);
+        assert!(uci.options.late_move_reductions);
+
+        uci.set_option("NullMove", "true");
+        assert!(uci.options.null_move);
+    }
+
+    #[test]
+    fn spin_options() {
+        let mut uci = Uci::new();
+
+        uci.set_option("Threads", "8");
+        assert_eq!(uci.options.threads, 8);
+
+        uci.set_option("Threads", "0");
+        assert_eq!(uci.options.threads, 8);
+
+        uci.set_option("MultiPV", "3");
+        assert_eq!(uci.options.multipv, 3);
+    }
+
+    #[test]
+    fn chess960_option() {
+        let mut uci = Uci::new();
+        assert!(!uci.chess960);
+
+        uci.set_option("UCI_Chess960", "true");
+        assert!(uci.chess960);
+
+        uci.handle("position fen 3k4/8/8/8/8/8/8/RK5R w HA - 0 1 moves b1a1");
+        assert_eq!(uci.board.to_fen(), "3k4/8/8/8/8/8/8/2KR3R b - - 1 1");
+    }
+
+    #[test]
+    fn new_game_starts_from_the_variant_position() {
+        let mut uci = Uci::new();
+        uci.set_option("UCI_Variant", "horde");
+        uci.handle("ucinewgame");
+        assert_eq!(uci.board.variant.name(), "horde");
+        assert_eq!(uci.board.to_fen(), variant::HORDE_FEN);
+    }
+
+    // A path of the test's own, so concurrent test runs don't share files.
+    fn temp_path(test: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("chessrs-{}-{}", std::process::id(), test))
+    }
+
+    #[test]
+    fn eval_file_option() {
+        let mut uci = Uci::new();
+        let path = temp_path("eval_file_option");
+        std::fs::write(&path, test_network(8).to_bytes()).unwrap();
+
+        uci.set_option("EvalFile", path.to_str().unwrap());
+        assert_eq!(uci.network.as_deref(), Some(&test_network(8)));
+
+        uci.set_option("UseNNUE", "false");
+        assert!(!uci.use_nnue);
+
+        uci.set_option("EvalFile", "<empty>");
+        assert!(uci.network.is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn book_moves_are_played_without_searching() {
+        let mut uci = Uci::new();
+        let e2e4 = parse_uci_move(&mut uci.board, "e2e4").unwrap();
+        let entry = BookEntry {
+            key: polyglot_key(&uci.board),
+            raw_move: encode_move(&e2e4),
+            weight: 1,
+            learn: 0,
+        };
+
+        let path = temp_path("book_moves_are_played_without_searching");
+        std::fs::write(&path, entry.to_bytes()).unwrap();
+
+        uci.set_option("BookFile", path.to_str().unwrap());
+        uci.set_option("BookSelection", "Best");
+        assert_eq!(uci.book_move(), Some(e2e4));
+
+        uci.handle("position startpos moves e2e4");
+        assert_eq!(uci.book_move(), None);
+
+        uci.set_option("BookFile", "<empty>");
+        assert!(uci.book.is_none());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn mate_score_format() {
+        assert_eq!(format_score(MATE - 1), "mate 1");
+        assert_eq!(format_score(-MATE + 2), "mate -1");
+        assert_eq!(format_score(35), "cp 35");
+    }
+}
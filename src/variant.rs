@@ -0,0 +1,435 @@
+use crate::{
+    board::{Board, STARTING_FEN_STRING},
+    movegen::{has_capture, is_attacked, king_in_check, legal_moves, GameStatus, Move},
+    piece::Color,
+};
+
+pub const ANTICHESS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1";
+pub const HORDE_FEN: &str =
+    "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+pub const RACING_KINGS_FEN: &str = "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1";
+
+// The rules a board is played under. Every hook defaults to standard chess,
+// so a variant only overrides the rules it changes. Chess960 isn't a variant:
+// it only moves the castling rooks, which every board keeps.
+pub trait Variant: Sync {
+    // The name used by UCI_Variant and the PGN Variant tag.
+    fn name(&self) -> &'static str;
+
+    fn start_fen(&self) -> &'static str {
+        STARTING_FEN_STRING
+    }
+
+    // Books, tablebases and endgame knowledge only hold under standard rules.
+    fn is_standard(&self) -> bool {
+        false
+    }
+
+    // Ranks pawns may push two squares from.
+    fn double_push_ranks(&self, color: Color) -> &'static [u8] {
+        match color {
+            Color::White => &[6],
+            Color::Black => &[1],
+        }
+    }
+
+    // Kinds a pawn can promote to, best first.
+    fn promotion_kinds(&self) -> &'static [usize] {
+        &[4, 3, 2, 1]
+    }
+
+    fn allows_castling(&self) -> bool {
+        true
+    }
+
+    // Whether kings can capture, and so attack the squares around them.
+    fn king_captures(&self) -> bool {
+        true
+    }
+
+    // Whether captured pieces go to the capturer's pocket to be dropped back.
+    fn has_drops(&self) -> bool {
+        false
+    }
+
+    // Whether the checks each side gives are counted and kept in the FEN.
+    fn counts_checks(&self) -> bool {
+        false
+    }
+
+    // Whether static exchange evaluation means anything.
+    fn has_exchanges(&self) -> bool {
+        true
+    }
+
+    // Removes generated moves the rules forbid. Whether a move stays mustn't
+    // depend on which other moves were generated.
+    fn filter_moves(&self, _board: &Board, _moves: &mut Vec<Move>) {}
+
+    // Runs once a move's pieces are in place, before the turn passes. It may
+    // only change what unmake_move restores: pieces through the board's own
+    // helpers, pockets, promoted pieces and checks.
+    fn after_move(&self, _board: &mut Board, _chess_move: &Move) {}
+
+    fn in_check(&self, board: &Board, color: Color) -> bool {
+        king_in_check(board, color)
+    }
+
+    // Whether the position a move by `color` led to breaks the rules.
+    fn is_illegal_position(&self, board: &Board, color: Color) -> bool {
+        self.in_check(board, color)
+    }
+
+    // Games decided by the variant's own rules rather than by mate, checked
+    // before the side to move gets to play.
+    fn status(&self, _board: &mut Board) -> Option<GameStatus> {
+        None
+    }
+
+    // The result when the side to move has no legal moves.
+    fn no_moves_status(&self, board: &Board) -> GameStatus {
+        if self.in_check(board, board.side_to_move) {
+            GameStatus::Checkmate(board.side_to_move.opposite())
+        } else {
+            GameStatus::Stalemate
+        }
+    }
+
+    // An evaluation replacing the general one, from White's point of view.
+    fn evaluate(&self, _board: &Board) -> Option<i32> {
+        None
+    }
+
+    // Progress towards the variant's own goal, from White's point of view.
+    fn eval_bonus(&self, _board: &Board) -> i32 {
+        0
+    }
+}
+
+pub struct Standard;
+pub struct Crazyhouse;
+pub struct Atomic;
+pub struct ThreeCheck;
+pub struct KingOfTheHill;
+pub struct RacingKings;
+pub struct Antichess;
+pub struct Horde;
+
+pub static VARIANTS: [&dyn Variant; 8] = [
+    &Standard,
+    &Crazyhouse,
+    &Atomic,
+    &ThreeCheck,
+    &KingOfTheHill,
+    &RacingKings,
+    &Antichess,
+    &Horde,
+];
+
+pub fn from_name(name: &str) -> Option<&'static dyn Variant> {
+    let name = name.to_ascii_lowercase();
+    let name = match name.as_str() {
+        "standard" => "chess",
+        "threecheck" | "three-check" => "3check",
+        "koth" | "king of the hill" => "kingofthehill",
+        "racing kings" => "racingkings",
+        "giveaway" | "losing chess" => "antichess",
+        name => name,
+    };
+
+    VARIANTS.into_iter().find(|variant| variant.name() == name)
+}
+
+// White's bonus less Black's.
+fn bonus_difference(bonus: impl Fn(Color) -> i32) -> i32 {
+    bonus(Color::White) - bonus(Color::Black)
+}
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "chess"
+    }
+
+    fn is_standard(&self) -> bool {
+        true
+    }
+}
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "crazyhouse"
+    }
+
+    fn has_drops(&self) -> bool {
+        true
+    }
+
+    fn after_move(&self, board: &mut Board, chess_move: &Move) {
+        board.update_pockets(chess_move);
+    }
+}
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "atomic"
+    }
+
+    fn king_captures(&self) -> bool {
+        false
+    }
+
+    // A capture blows up both pieces, so nothing is exchanged.
+    fn has_exchanges(&self) -> bool {
+        false
+    }
+
+    fn after_move(&self, board: &mut Board, chess_move: &Move) {
+        if chess_move.is_capture() {
+            board.explode(chess_move.to);
+        }
+    }
+
+    // A king that has exploded counts as permanently in check, which makes
+    // blowing up your own king illegal and leaves its side mated. Blowing up
+    // the enemy king wins regardless, and kings next to each other can't be
+    // checked since capturing one would explode both.
+    fn in_check(&self, board: &Board, color: Color) -> bool {
+        let Some(king_square) = board.king_square(color) else {
+            return true;
+        };
+        let Some(enemy_king) = board.king_square(color.opposite()) else {
+            return false;
+        };
+
+        let touching =
+            king_square.0.abs_diff(enemy_king.0) <= 1 && king_square.1.abs_diff(enemy_king.1) <= 1;
+        !touching && is_attacked(board, king_square, color.opposite())
+    }
+}
+
+// Checks already given, by count.
+const CHECK_BONUS: [i32; 4] = [0, 80, 250, 0];
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "3check"
+    }
+
+    fn counts_checks(&self) -> bool {
+        true
+    }
+
+    fn after_move(&self, board: &mut Board, _chess_move: &Move) {
+        let color = board.side_to_move;
+        if self.in_check(board, color.opposite()) {
+            board.checks[color.index() - 1] += 1;
+        }
+    }
+
+    fn status(&self, board: &mut Board) -> Option<GameStatus> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|color| board.checks[color.index() - 1] >= 3)
+            .map(GameStatus::VariantWin)
+    }
+
+    fn eval_bonus(&self, board: &Board) -> i32 {
+        bonus_difference(|color| CHECK_BONUS[board.checks[color.index() - 1].min(3) as usize])
+    }
+}
+
+const HILL: [(u8, u8); 4] = [(3, 3), (3, 4), (4, 3), (4, 4)];
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "kingofthehill"
+    }
+
+    fn status(&self, board: &mut Board) -> Option<GameStatus> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|color| {
+                board
+                    .king_square(*color)
+                    .is_some_and(|king| HILL.contains(&king))
+            })
+            .map(GameStatus::VariantWin)
+    }
+
+    // The king's distance from the hill.
+    fn eval_bonus(&self, board: &Board) -> i32 {
+        bonus_difference(|color| match board.king_square(color) {
+            Some((rank, file)) => {
+                let distance =
+                    rank.abs_diff(3).min(rank.abs_diff(4)) + file.abs_diff(3).min(file.abs_diff(4));
+                30 * (6 - distance as i32)
+            }
+            None => 0,
+        })
+    }
+}
+
+impl Variant for RacingKings {
+    fn name(&self) -> &'static str {
+        "racingkings"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        RACING_KINGS_FEN
+    }
+
+    // Giving check is as illegal as being left in it.
+    fn is_illegal_position(&self, board: &Board, color: Color) -> bool {
+        self.in_check(board, color) || self.in_check(board, color.opposite())
+    }
+
+    fn status(&self, board: &mut Board) -> Option<GameStatus> {
+        let home = |color| board.king_square(color).is_some_and(|king| king.0 == 0);
+        match (home(Color::White), home(Color::Black)) {
+            (true, true) => Some(GameStatus::VariantDraw),
+            (false, true) => Some(GameStatus::VariantWin(Color::Black)),
+            // Black gets one more move to draw by reaching the last rank as
+            // well.
+            (true, false) => {
+                let catches_up = board.side_to_move == Color::Black
+                    && legal_moves(board)
+                        .iter()
+                        .any(|chess_move| chess_move.piece.is_king() && chess_move.to.0 == 0);
+                (!catches_up).then_some(GameStatus::VariantWin(Color::White))
+            }
+            (false, false) => None,
+        }
+    }
+
+    // The king's rank in the race.
+    fn eval_bonus(&self, board: &Board) -> i32 {
+        bonus_difference(|color| {
+            board
+                .king_square(color)
+                .map_or(0, |(rank, _)| 60 * (7 - rank as i32))
+        })
+    }
+}
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "antichess"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        ANTICHESS_FEN
+    }
+
+    fn promotion_kinds(&self) -> &'static [usize] {
+        &[4, 3, 2, 1, 5]
+    }
+
+    fn allows_castling(&self) -> bool {
+        false
+    }
+
+    // Recaptures are forced, so exchanges don't stop when they stop paying.
+    fn has_exchanges(&self) -> bool {
+        false
+    }
+
+    // Captures are compulsory.
+    fn filter_moves(&self, board: &Board, moves: &mut Vec<Move>) {
+        if has_capture(board) {
+            moves.retain(Move::is_capture);
+        }
+    }
+
+    // The king is an ordinary piece.
+    fn in_check(&self, _board: &Board, _color: Color) -> bool {
+        false
+    }
+
+    // Losing every piece wins, like being stalemated.
+    fn status(&self, board: &mut Board) -> Option<GameStatus> {
+        let color = board.side_to_move;
+        (board.occupied[color.index()].count() == 0).then_some(GameStatus::VariantWin(color))
+    }
+
+    fn no_moves_status(&self, board: &Board) -> GameStatus {
+        GameStatus::VariantWin(board.side_to_move)
+    }
+
+    // Fewer pieces left is better, which neither the network nor the classic
+    // evaluation know.
+    fn evaluate(&self, board: &Board) -> Option<i32> {
+        Some(100 * (board.occupied[2].count() as i32 - board.occupied[1].count() as i32))
+    }
+}
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "horde"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        HORDE_FEN
+    }
+
+    // The horde's pawns on the first rank can push two squares as well.
+    fn double_push_ranks(&self, color: Color) -> &'static [u8] {
+        match color {
+            Color::White => &[6, 7],
+            Color::Black => &[1],
+        }
+    }
+
+    // The horde loses once every pawn is gone; it has no king to mate.
+    fn status(&self, board: &mut Board) -> Option<GameStatus> {
+        (board.occupied[Color::White.index()].count() == 0)
+            .then_some(GameStatus::VariantWin(Color::Black))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::{game_status, parse_uci_move};
+
+    #[test]
+    fn names_round_trip() {
+        for variant in VARIANTS {
+            assert_eq!(from_name(variant.name()).unwrap().name(), variant.name());
+        }
+        assert!(from_name("Standard").unwrap().is_standard());
+        assert_eq!(from_name("Crazyhouse").unwrap().name(), "crazyhouse");
+        assert_eq!(from_name("threeCheck").unwrap().name(), "3check");
+        assert!(from_name("shogi").is_none());
+    }
+
+    // The first capture wins, with everything else as in standard chess.
+    struct FirstBlood;
+
+    impl Variant for FirstBlood {
+        fn name(&self) -> &'static str {
+            "firstblood"
+        }
+
+        fn status(&self, board: &mut Board) -> Option<GameStatus> {
+            (board.occupied[0].count() < 32)
+                .then_some(GameStatus::VariantWin(board.side_to_move.opposite()))
+        }
+    }
+
+    #[test]
+    fn rules_outside_movegen() {
+        let mut board = Board::from_variant_fen(
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2",
+            &FirstBlood,
+        )
+        .unwrap();
+        assert_eq!(game_status(&mut board), GameStatus::Ongoing);
+
+        let capture = parse_uci_move(&mut board, "e4d5").unwrap();
+        board.make_move(&capture);
+        assert_eq!(
+            game_status(&mut board),
+            GameStatus::VariantWin(Color::White)
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    io::BufRead,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{
+    board::{Board, STARTING_FEN_STRING},
+    movegen::{game_status, parse_uci_move, GameStatus, Move},
+    piece::Color,
+    search::{SearchInfo, SearchLimits, SearchOptions, Searcher, MATE, MAX_PLY},
+    tt::TranspositionTable,
+};
+
+const ENGINE_NAME: &str = "chessrs";
+
+enum Event {
+    Command(String),
+    BestMove(u32, Option<Move>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeControl {
+    pub moves_per_session: Option<u32>,
+    pub base: u64,
+    pub increment: u64,
+}
+
+// Parses the arguments of "level MPS BASE INC", where BASE is either minutes
+// or "minutes:seconds" and INC is in (possibly fractional) seconds.
+pub fn parse_level(args: &[&str]) -> Option<TimeControl> {
+    let [moves_per_session, base, increment] = args else {
+        return None;
+    };
+
+    let moves_per_session = moves_per_session.parse::<u32>().ok()?;
+
+    let base = match base.split_once(':') {
+        Some((minutes, seconds)) => {
+            minutes.parse::<u64>().ok()? * 60_000 + seconds.parse::<u64>().ok()? * 1000
+        }
+        None => base.parse::<u64>().ok()? * 60_000,
+    };
+
+    let increment = (increment.parse::<f64>().ok()? * 1000.0) as u64;
+
+    Some(TimeControl {
+        moves_per_session: (moves_per_session > 0).then_some(moves_per_session),
+        base,
+        increment,
+    })
+}
+
+pub fn format_post(info: &SearchInfo) -> String {
+    let score = if info.score.abs() >= MATE - MAX_PLY as i32 {
+        let moves = (MATE - info.score.abs() + 1) / 2;
+        if info.score > 0 {
+            100000 + moves
+        } else {
+            -100000 - moves
+        }
+    } else {
+        info.score
+    };
+
+    let pv: Vec<_> = info.pv.iter().map(|m| m.to_uci()).collect();
+
+    format!(
+        "{} {} {} {} {}",
+        info.depth,
+        score,
+        info.time.as_millis() / 10,
+        info.nodes,
+        pv.join(" ")
+    )
+}
+
+fn result_string(status: GameStatus) -> Option<&'static str> {
+    match status {
+        GameStatus::Ongoing => None,
+        GameStatus::Checkmate(Color::White) => Some("1-0 {White mates}"),
+        GameStatus::Checkmate(Color::Black) => Some("0-1 {Black mates}"),
+        GameStatus::Stalemate => Some("1/2-1/2 {Stalemate}"),
+        GameStatus::FiftyMoveRule => Some("1/2-1/2 {Draw by fifty move rule}"),
+        GameStatus::Repetition => Some("1/2-1/2 {Draw by repetition}"),
+        GameStatus::VariantWin(Color::White) => Some("1-0 {White wins}"),
+        GameStatus::VariantWin(Color::Black) => Some("0-1 {Black wins}"),
+        GameStatus::VariantDraw => Some("1/2-1/2 {Draw}"),
+    }
+}
+
+struct XBoard {
+    board: Board,
+    played: Vec<Move>,
+    engine_color: Option<Color>,
+    post: bool,
+    time_control: Option<TimeControl>,
+    move_time: Option<u64>,
+    depth: Option<u32>,
+    engine_time: Option<u64>,
+    opponent_time: Option<u64>,
+    options: SearchOptions,
+    tt: Arc<TranspositionTable>,
+    stop: Arc<AtomicBool>,
+    search_id: u32,
+    events: Sender<Event>,
+}
+
+impl XBoard {
+    fn new(events: Sender<Event>) -> Self {
+        Self {
+            board: Board::from_fen(STARTING_FEN_STRING).unwrap(),
+            played: Vec::new(),
+            engine_color: Some(Color::Black),
+            post: false,
+            time_control: None,
+            move_time: None,
+            depth: None,
+            engine_time: None,
+            opponent_time: None,
+            options: SearchOptions::default(),
+            tt: Arc::new(TranspositionTable::new(SearchOptions::default().hash)),
+            stop: Arc::new(AtomicBool::new(false)),
+            search_id: 0,
+            events,
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let mut limits = SearchLimits {
+            depth: self.depth,
+            movetime: self.move_time,
+            ..Default::default()
+        };
+
+        if let (None, Some(engine_time)) = (self.move_time, self.engine_time) {
+            let increment = self.time_control.map(|tc| tc.increment);
+            let opponent_time = self.opponent_time.or(Some(engine_time));
+
+            (limits.wtime, limits.btime, limits.winc, limits.binc) = match self.board.side_to_move {
+                Color::White => (Some(engine_time), opponent_time, increment, increment),
+                Color::Black => (opponent_time, Some(engine_time), increment, increment),
+            };
+
+            limits.movestogo = self
+                .time_control
+                .and_then(|tc| tc.moves_per_session)
+                .map(|moves| moves - (self.board.fullmove_number - 1) % moves);
+        }
+
+        limits
+    }
+
+    // Abandons the running search, if any, without playing its result.
+    fn cancel_search(&mut self) {
+        self.search_id += 1;
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn start_search(&mut self) {
+        self.cancel_search();
+
+        self.stop = Arc::new(AtomicBool::new(false));
+        let stop = self.stop.clone();
+        let board = self.board.clone();
+        let limits = self.limits();
+        let post = self.post;
+        let options = self.options;
+        let tt = self.tt.clone();
+        let search_id = self.search_id;
+        let events = self.events.clone();
+
+        thread::spawn(move || {
+            let mut searcher = Searcher::with_tt(board, limits, options, stop, tt);
+            let best_move = searcher.search(|info| {
+                if post {
+                    println!("{}", format_post(info));
+                }
+            });
+
+            let _ = events.send(Event::BestMove(search_id, best_move));
+        });
+    }
+
+    fn engine_to_move(&self) -> bool {
+        self.engine_color == Some(self.board.side_to_move)
+    }
+
+    // Plays a move on the board and reports the result if it ended the game.
+    fn play(&mut self, chess_move: Move) {
+        self.board.make_move(&chess_move);
+        self.played.push(chess_move);
+
+        if let Some(result) = result_string(game_status(&mut self.board)) {
+            println!("{}", result);
+            self.engine_color = None;
+        }
+    }
+
+    fn take_back(&mut self, count: usize) {
+        self.cancel_search();
+
+        for _ in 0..count {
+            if let Some(chess_move) = self.played.pop() {
+                self.board.unmake_move(&chess_move);
+            }
+        }
+    }
+
+    fn user_move(&mut self, uci: &str) {
+        match parse_uci_move(&mut self.board, uci) {
+            Some(chess_move) => {
+                self.play(chess_move);
+                if self.engine_to_move() {
+                    self.start_search();
+                }
+            }
+            None => println!("Illegal move: {}", uci),
+        }
+    }
+
+    fn handle_best_move(&mut self, search_id: u32, best_move: Option<Move>) {
+        if search_id != self.search_id || !self.engine_to_move() {
+            return;
+        }
+
+        if let Some(best_move) = best_move {
+            println!("move {}", best_move.to_uci());
+            self.play(best_move);
+        }
+    }
+
+    fn handle(&mut self, line: &str) -> bool {
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        let Some((command, args)) = tokens.split_first() else {
+            return true;
+        };
+
+        match *command {
+            "protover" => {
+                println!(
+                    "feature myname=\"{}\" ping=1 setboard=1 usermove=1 san=0 colors=0 \
+                     sigint=0 sigterm=0 analyze=0 smp=1 memory=1 done=1",
+                    ENGINE_NAME
+                );
+            }
+            "new" => {
+                self.cancel_search();
+                self.board = Board::from_fen(STARTING_FEN_STRING).unwrap();
+                self.played.clear();
+                self.engine_color = Some(Color::Black);
+                self.move_time = None;
+                self.depth = None;
+                self.tt.clear();
+            }
+            "force" => {
+                self.cancel_search();
+                self.engine_color = None;
+            }
+            "go" => {
+                self.engine_color = Some(self.board.side_to_move);
+                self.start_search();
+            }
+            "usermove" => match args.first() {
+                Some(uci) => self.user_move(uci),
+                None => println!("Error (missing move): usermove"),
+            },
+            "setboard" => match Board::from_fen(&args.join(" ")) {
+                Some(board) => {
+                    self.cancel_search();
+                    self.board = board;
+                    self.played.clear();
+                }
+                None => println!("tellusererror Illegal position"),
+            },
+            "level" => match parse_level(args) {
+                Some(time_control) => {
+                    self.time_control = Some(time_control);
+                    self.move_time = None;
+                }
+                None => println!("Error (invalid level): {}", line),
+            },
+            "st" => {
+                self.move_time = args
+                    .first()
+                    .and_then(|seconds| seconds.parse::<u64>().ok())
+                    .map(|seconds| seconds * 1000);
+            }
+            "sd" => self.depth = args.first().and_then(|depth| depth.parse().ok()),
+            "time" => {
+                self.engine_time = args
+                    .first()
+                    .and_then(|time| time.parse::<u64>().ok())
+                    .map(|centiseconds| centiseconds * 10);
+            }
+            "otim" => {
+                self.opponent_time = args
+                    .first()
+                    .and_then(|time| time.parse::<u64>().ok())
+                    .map(|centiseconds| centiseconds * 10);
+            }
+            "cores" => match args.first().and_then(|cores| cores.parse::<usize>().ok()) {
+                Some(cores) if cores > 0 => self.options.threads = cores,
+                _ => println!("Error (invalid core count): {}", line),
+            },
+            "memory" => match args.first().and_then(|memory| memory.parse::<usize>().ok()) {
+                Some(memory) => {
+                    self.cancel_search();
+                    self.options.hash = memory;
+                    self.tt = Arc::new(TranspositionTable::new(memory));
+                }
+                None => println!("Error (invalid memory size): {}", line),
+            },
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "result" => {
+                self.cancel_search();
+                self.engine_color = None;
+            }
+            "ping" => println!("pong {}", args.first().unwrap_or(&"")),
+            "?" => self.stop.store(true, Ordering::Relaxed),
+            "quit" => {
+                self.cancel_search();
+                return false;
+            }
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "name" | "rating" | "ics" => {}
+            _ => println!("Error (unknown command): {}", command),
+        }
+
+        true
+    }
+}
+
+pub fn run() {
+    let (events, receiver) = mpsc::channel();
+
+    let commands = events.clone();
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            if commands.send(Event::Command(line)).is_err() {
+                break;
+            }
+        }
+
+        let _ = commands.send(Event::Command("quit".to_string()));
+    });
+
+    let mut xboard = XBoard::new(events);
+    for event in receiver {
+        match event {
+            Event::Command(line) => {
+                if !xboard.handle(&line) {
+                    return;
+                }
+            }
+            Event::BestMove(search_id, best_move) => xboard.handle_best_move(search_id, best_move),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xboard() -> XBoard {
+        let (events, _) = mpsc::channel();
+        XBoard::new(events)
+    }
+
+    #[test]
+    fn level_parsing() {
+        assert_eq!(
+            parse_level(&["40", "5", "0"]),
+            Some(TimeControl {
+                moves_per_session: Some(40),
+                base: 300_000,
+                increment: 0,
+            })
+        );
+        assert_eq!(
+            parse_level(&["0", "2:30", "1.5"]),
+            Some(TimeControl {
+                moves_per_session: None,
+                base: 150_000,
+                increment: 1500,
+            })
+        );
+        assert_eq!(parse_level(&["40", "5"]), None);
+    }
+
+    #[test]
+    fn force_mode_moves_and_undo() {
+        let mut xboard = xboard();
+
+        xboard.handle("force");
+        xboard.handle("usermove e2e4");
+        xboard.handle("usermove e7e5");
+        assert_eq!(
+            xboard.board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+
+        xboard.handle("undo");
+        assert_eq!(xboard.board.side_to_move, Color::Black);
+
+        xboard.handle("remove");
+        assert_eq!(xboard.board.to_fen(), STARTING_FEN_STRING);
+    }
+
+    #[test]
+    fn clock_limits() {
+        let mut xboard = xboard();
+
+        xboard.handle("setboard 4k3/8/8/8/8/8/4P3/4K3 b - - 0 11");
+        xboard.handle("level 40 5 2");
+        xboard.handle("time 6000");
+        xboard.handle("otim 3000");
+
+        let limits = xboard.limits();
+        assert_eq!(limits.btime, Some(60_000));
+        assert_eq!(limits.wtime, Some(30_000));
+        assert_eq!(limits.binc, Some(2000));
+        assert_eq!(limits.movestogo, Some(30));
+
+        xboard.handle("st 3");
+        xboard.handle("sd 6");
+        assert_eq!(xboard.limits().movetime, Some(3000));
+        assert_eq!(xboard.limits().depth, Some(6));
+    }
+
+    #[test]
+    fn cores_and_memory() {
+        let mut xboard = xboard();
+
+        xboard.handle("cores 4");
+        xboard.handle("memory 32");
+        assert_eq!(xboard.options.threads, 4);
+        assert_eq!(xboard.options.hash, 32);
+
+        xboard.handle("cores 0");
+        assert_eq!(xboard.options.threads, 4);
+    }
+}
@@ -0,0 +1,45 @@
+pub const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+    (state, z ^ (z >> 31))
+}
+
+const fn random_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+
+    let mut index = 0;
+    while index < N {
+        let (next_state, key) = split_mix(state);
+        state = next_state;
+        keys[index] = key;
+        index += 1;
+    }
+
+    keys
+}
+
+pub const PIECE_KEYS: [u64; 768] = random_keys(0x636865737372730a);
+pub const CASTLING_KEYS: [u64; 4] = random_keys(0x636173746c696e67);
+pub const EN_PASSANT_KEYS: [u64; 8] = random_keys(0x656e70617373616e);
+pub const SIDE_KEY: u64 = random_keys::<1>(0x73696465746f6d76)[0];
+pub const POCKET_KEYS: [u64; 160] = random_keys(0x706f636b65747321);
+pub const CHECK_KEYS: [u64; 6] = random_keys(0x636865636b733321);
+
+// Key for holding `count` (1 to 16) pieces of a kind in hand.
+pub fn pocket_key(color_index: usize, kind: usize, count: u8) -> u64 {
+    POCKET_KEYS[(color_index * 5 + kind) * 16 + (count as usize - 1).min(15)]
+}
+
+// Key for having given `count` (1 to 3) checks in three-check.
+pub fn check_key(color_index: usize, count: u8) -> u64 {
+    CHECK_KEYS[color_index * 3 + (count as usize - 1).min(2)]
+}
+
+pub fn piece_key(piece_index: usize, square: u8) -> u64 {
+    PIECE_KEYS[piece_index * 64 + square as usize]
+}